@@ -0,0 +1,60 @@
+//Instructions per second of the core with nothing else running, per opcode class and
+//for any roms copied into tests/roms. Needs --features bench, add trace to see what tracing costs
+use std::fs;
+
+use chip8_emulator::{
+    chip8::Chip8,
+    workloads::{self, workloads},
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+//Instructions per iteration
+const INSTRUCTIONS: u64 = 10_000;
+const TICK_RATE: u32 = 15;
+const ROM_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms");
+
+fn opcode_classes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("class");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for workload in workloads() {
+        let mut cpu = workload.cpu();
+        group.bench_function(workload.class, |b| b.iter(|| workloads::run(&mut cpu, INSTRUCTIONS, TICK_RATE)));
+    }
+    group.finish();
+}
+
+fn roms(c: &mut Criterion) {
+    let Ok(entries) = fs::read_dir(ROM_DIR) else {
+        eprintln!("No roms in {}, only the opcode classes are measured", ROM_DIR);
+        return;
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "ch8"))
+        .collect();
+    paths.sort();
+    let mut group = c.benchmark_group("rom");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for path in paths {
+        let Ok(rom) = fs::read(&path) else { continue };
+        let boot = || Chip8::new().with_rom(&rom).with_seed(0);
+        let mut cpu = boot();
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        //Roms that stop for a key or on an error start over, so every iteration runs all its instructions
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut left = INSTRUCTIONS;
+                while left > 0 {
+                    left -= workloads::run(&mut cpu, left, TICK_RATE);
+                    if left > 0 {
+                        cpu = boot();
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, opcode_classes, roms);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#!/bin/sh
+#Replaces the empty programs.json with the community chip-8-database and saves its license
+#next to it. REF picks the branch, tag or commit to take them from
+set -eu
+cd "$(dirname "$0")"
+base="https://raw.githubusercontent.com/chip-8/chip-8-database/${REF:-master}"
+curl -fsSL "$base/database/programs.json" -o programs.json.new
+curl -fsSL "$base/LICENSE" -o LICENSE
+mv programs.json.new programs.json
+echo "Fetched $(grep -c '"title"' programs.json) programs, rebuild to bundle them"
@@ -0,0 +1,43 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip8_emulator::{chip8::quirks::Quirks, reference::Lockstep};
+use libfuzzer_sys::fuzz_target;
+
+//Frames per input, a few hundred instructions at the tick rate below
+const FRAMES: u64 = 40;
+const TICK_RATE: u32 = 15;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    //logic, shift, memory_leave_i, jump, wrap and vblank
+    quirks: [bool; 6],
+    seed: u64,
+    rom: Vec<u8>,
+    //Key changes as (frame, key, pressed), applied at the start of their frame
+    keys: Vec<(u8, u8, bool)>,
+}
+
+//The core and the reference interpreter should agree on every rom, any difference is a crash
+fuzz_target!(|input: Input| {
+    let [logic, shift, memory_leave_i, jump, wrap, vblank] = input.quirks;
+    let quirks = Quirks {
+        logic,
+        shift,
+        memory_leave_i,
+        jump,
+        wrap,
+        vblank,
+    };
+    let mut lockstep = Lockstep::new(&input.rom, quirks, input.seed);
+    for frame in 0..FRAMES {
+        for &(_, key, pressed) in input.keys.iter().filter(|(at, ..)| u64::from(*at) == frame) {
+            if let Err(e) = lockstep.set_key(key, pressed) {
+                panic!("{}", e);
+            }
+        }
+        if let Err(e) = lockstep.run_frame(TICK_RATE) {
+            panic!("{}", e);
+        }
+    }
+});
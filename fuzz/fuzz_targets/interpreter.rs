@@ -0,0 +1,47 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip8_emulator::chip8::{quirks::Quirks, Chip8};
+use libfuzzer_sys::fuzz_target;
+
+//Enough for a rom's loops to come round many times while keeping runs quick
+const MAX_STEPS: u32 = 10_000;
+//Instructions between timer ticks, roughly what the frontend runs per frame
+const STEPS_PER_FRAME: u32 = 16;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    //logic, shift, memory_leave_i, jump, wrap and vblank
+    quirks: [bool; 6],
+    rom: Vec<u8>,
+    //Key changes as (step, key, pressed), each applied once its step comes up, in any order
+    keys: Vec<(u16, u8, bool)>,
+}
+
+//Any rom and any keys should run without panicking, faults come back as CPUErrors
+fuzz_target!(|input: Input| {
+    let [logic, shift, memory_leave_i, jump, wrap, vblank] = input.quirks;
+    let quirks = Quirks {
+        logic,
+        shift,
+        memory_leave_i,
+        jump,
+        wrap,
+        vblank,
+    };
+    let mut cpu = Chip8::new().with_quirks(quirks).with_rom(&input.rom);
+    //Sorted so a late key does not hold back the ones before it, changes on the same step keep their order
+    let mut keys = input.keys;
+    keys.sort_by_key(|&(at, ..)| at);
+    let mut keys = keys.into_iter().peekable();
+    for step in 0..MAX_STEPS {
+        while let Some((_, key, pressed)) = keys.next_if(|(at, ..)| u32::from(*at) <= step) {
+            cpu.set_key(key, pressed);
+        }
+        cpu.update();
+        if step % STEPS_PER_FRAME == STEPS_PER_FRAME - 1 {
+            cpu.update_timers();
+        }
+    }
+    let _ = cpu.get_display_buffer();
+});
@@ -0,0 +1,43 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+//One triangle covering the whole history texture
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.tex_coords = uv;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_screen: texture_2d<f32>;
+@group(0) @binding(1)
+var s_screen: sampler;
+
+@group(1) @binding(0)
+var t_history: texture_2d<f32>;
+@group(1) @binding(1)
+var s_history: sampler;
+
+struct Effects {
+    decay: f32,
+    scanlines: f32,
+    curvature: f32,
+    bloom: f32,
+};
+
+@group(2) @binding(0)
+var<uniform> effects: Effects;
+
+//Brightness of each pixel, lit pixels are full and unlit ones fade out
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let lit = textureSample(t_screen, s_screen, in.tex_coords).x;
+    let previous = textureSample(t_history, s_history, in.tex_coords).x;
+    let brightness = max(lit, previous * effects.decay);
+    return vec4<f32>(brightness, brightness, brightness, 1.0);
+}
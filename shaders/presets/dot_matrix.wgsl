@@ -0,0 +1,38 @@
+//Single pass preset: every Chip-8 pixel drawn as a round dot
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+//Presets draw one triangle covering their target
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.uv = uv;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+//Background, first plane, second plane and both planes
+struct Palette {
+    colors: array<vec4<f32>, 4>,
+};
+
+@group(0) @binding(0)
+var<uniform> palette: Palette;
+
+@group(1) @binding(0)
+var s_input: sampler;
+//Brightness of each Chip-8 pixel
+@group(1) @binding(1)
+var t_screen: texture_2d<f32>;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_screen));
+    let brightness = textureSample(t_screen, s_input, in.uv).x;
+    let cell = fract(in.uv * size) - 0.5;
+    let dot = 1.0 - smoothstep(0.3, 0.45, length(cell));
+    return mix(palette.colors[0], palette.colors[1], brightness * dot);
+}
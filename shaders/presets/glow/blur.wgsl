@@ -0,0 +1,40 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.uv = uv;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+struct Palette {
+    colors: array<vec4<f32>, 4>,
+};
+
+@group(0) @binding(0)
+var<uniform> palette: Palette;
+
+@group(1) @binding(0)
+var s_input: sampler;
+@group(1) @binding(1)
+var t_screen: texture_2d<f32>;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_screen));
+    var glow = 0.0;
+    for (var x = -3; x <= 3; x++) {
+        for (var y = -3; y <= 3; y++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            let weight = exp(-dot(offset, offset) / 4.0);
+            glow += textureSample(t_screen, s_input, in.uv + offset * texel * 0.5).x * weight;
+        }
+    }
+    glow /= 12.0;
+    return vec4<f32>(glow, glow, glow, 1.0);
+}
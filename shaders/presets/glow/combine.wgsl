@@ -0,0 +1,35 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.uv = uv;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+struct Palette {
+    colors: array<vec4<f32>, 4>,
+};
+
+@group(0) @binding(0)
+var<uniform> palette: Palette;
+
+@group(1) @binding(0)
+var s_input: sampler;
+@group(1) @binding(1)
+var t_screen: texture_2d<f32>;
+@group(1) @binding(2)
+var t_blur: texture_2d<f32>;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let brightness = textureSample(t_screen, s_input, in.uv).x;
+    let glow = textureSample(t_blur, s_input, in.uv).x;
+    let color = mix(palette.colors[0], palette.colors[1], brightness);
+    return vec4<f32>(color.xyz + palette.colors[1].xyz * glow * 0.6, 1.0);
+}
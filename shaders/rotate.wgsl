@@ -0,0 +1,29 @@
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+//Same quad as the built-in shader, its texture coordinates already turned
+@vertex
+fn vertex(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = in.tex_coords;
+    out.position = vec4<f32>(in.pos, 0.0, 1.0);
+    return out;
+}
+
+//A shader preset's picture, drawn upright before being turned onto the window
+@group(0) @binding(0)
+var t_picture: texture_2d<f32>;
+@group(0) @binding(1)
+var s_picture: sampler;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_picture, s_picture, in.tex_coords);
+}
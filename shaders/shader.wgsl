@@ -16,24 +16,65 @@ fn vertex(in: VertexInput) -> VertexOutput {
     return out;
 }
 
+//Brightness of each pixel from the persistence pass
 @group(0) @binding(0)
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
-const COLOR: vec3<f32> = vec3<f32>(0.005,0.01,0.005);
+//Background, first plane, second plane and both planes
+struct Palette {
+    colors: array<vec4<f32>, 4>,
+};
+
+@group(1) @binding(0)
+var<uniform> palette: Palette;
+
+struct Effects {
+    decay: f32,
+    scanlines: f32,
+    curvature: f32,
+    bloom: f32,
+};
 
-fn is_white(v: vec3<f32>) -> bool {
-    return v.x == 1.0 && v.y == 1.0 && v.z == 1.0;
+@group(2) @binding(0)
+var<uniform> effects: Effects;
+
+const PI: f32 = 3.14159265;
+
+//Bends the picture like the glass of a tube
+fn curve(uv: vec2<f32>) -> vec2<f32> {
+    let centered = uv * 2.0 - 1.0;
+    let bent = centered * (1.0 + effects.curvature * dot(centered.yx, centered.yx));
+    return bent * 0.5 + 0.5;
+}
+
+fn brightness(uv: vec2<f32>) -> f32 {
+    return textureSampleLevel(t_diffuse, s_diffuse, uv, 0.0).x;
 }
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    let c = textureSample(t_diffuse, s_diffuse, in.tex_coords).xyz;
-    //Opposite due to pnm file format
-    if is_white(c) {
-        return vec4<f32>(COLOR, 0.0);
-    } else {
-        return vec4<f32>(COLOR * 30.0, 0.0); 
+    let uv = curve(in.tex_coords);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    var color = mix(palette.colors[0], palette.colors[1], brightness(uv)).xyz;
+
+    if effects.bloom > 0.0 {
+        let texel = 1.0 / vec2<f32>(textureDimensions(t_diffuse));
+        var glow = 0.0;
+        for (var x = -2; x <= 2; x++) {
+            for (var y = -2; y <= 2; y++) {
+                glow += brightness(uv + vec2<f32>(f32(x), f32(y)) * texel);
+            }
+        }
+        color += palette.colors[1].xyz * glow / 25.0 * effects.bloom;
     }
-}
\ No newline at end of file
+
+    //Darkens the gaps between the rows of Chip-8 pixels
+    let row = fract(uv.y * f32(textureDimensions(t_diffuse).y));
+    color *= 1.0 - effects.scanlines * (1.0 - sin(row * PI));
+
+    return vec4<f32>(color, 1.0);
+}
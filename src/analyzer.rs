@@ -0,0 +1,185 @@
+use std::fmt;
+
+use crate::chip8::{quirks::Platform, ENTRY_POINT};
+
+//Evidence lines printed per platform, the rest are only counted
+const MAX_LOGGED: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Evidence {
+    pub addr: u16,
+    pub op: u16,
+    pub platform: Platform,
+    pub reason: &'static str,
+}
+
+pub struct Analysis {
+    pub platform: Platform,
+    pub evidence: Vec<Evidence>,
+}
+
+//Walks the rom from the entry point following jumps, calls and skips so that
+//sprite data is not mistaken for instructions, collecting platform specific opcodes
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let fetch = |addr: u16| -> Option<u16> {
+        let i = (addr as usize).checked_sub(ENTRY_POINT as usize)?;
+        Some(u16::from_be_bytes([*rom.get(i)?, *rom.get(i + 1)?]))
+    };
+    let mut visited = [false; 0x1000];
+    let mut queue = vec![ENTRY_POINT];
+    let mut evidence = Vec::new();
+
+    while let Some(addr) = queue.pop() {
+        if visited.get(addr as usize).copied().unwrap_or(true) {
+            continue;
+        }
+        let Some(op) = fetch(addr) else { continue };
+        visited[addr as usize] = true;
+        if let Some((platform, reason)) = classify(op) {
+            evidence.push(Evidence { addr, op, platform, reason });
+        }
+
+        let next = addr.wrapping_add(2);
+        //XO-CHIP skips jump over the whole of a four byte F000 instruction
+        let skip = match fetch(next) {
+            Some(0xF000) => next.wrapping_add(4),
+            _ => next.wrapping_add(2),
+        };
+        match op {
+            //Returns, exit and computed jumps end the path
+            0x00EE | 0x00FD | 0xB000..=0xBFFF => (),
+            0x1000..=0x1FFF => queue.push(op & 0x0FFF),
+            0x2000..=0x2FFF => queue.extend([op & 0x0FFF, next]),
+            0x3000..=0x4FFF => queue.extend([next, skip]),
+            0x5000..=0x5FFF | 0x9000..=0x9FFF if op & 0xF == 0 => queue.extend([next, skip]),
+            0xE000..=0xEFFF if matches!(op & 0xFF, 0x9E | 0xA1) => queue.extend([next, skip]),
+            0xF000 => queue.push(addr.wrapping_add(4)),
+            _ => queue.push(next),
+        }
+    }
+
+    let found = |platform| evidence.iter().any(|e| e.platform == platform);
+    let platform = if found(Platform::XoChip) {
+        Platform::XoChip
+    } else if found(Platform::SuperChip) {
+        Platform::SuperChip
+    } else {
+        Platform::Chip8
+    };
+    Analysis { platform, evidence }
+}
+
+fn classify(op: u16) -> Option<(Platform, &'static str)> {
+    let (x, lb) = ((op >> 8) & 0xF, op & 0xFF);
+    let evidence = match op & 0xF000 {
+        0x0000 => match op {
+            //Blank memory is run as a NOP by this emulator
+            0x0000 | 0x00E0 | 0x00EE => return None,
+            0x00C0..=0x00CF => (Platform::SuperChip, "scroll down"),
+            0x00D0..=0x00DF => (Platform::XoChip, "scroll up"),
+            0x00FB => (Platform::SuperChip, "scroll right"),
+            0x00FC => (Platform::SuperChip, "scroll left"),
+            0x00FD => (Platform::SuperChip, "exit"),
+            0x00FE => (Platform::SuperChip, "lores"),
+            0x00FF => (Platform::SuperChip, "hires"),
+            _ => (Platform::Chip8, "machine code call, COSMAC VIP only"),
+        },
+        0x5000 => match op & 0xF {
+            0x2 => (Platform::XoChip, "save vx - vy"),
+            0x3 => (Platform::XoChip, "load vx - vy"),
+            _ => return None,
+        },
+        0xD000 if op & 0xF == 0 => (Platform::SuperChip, "16x16 sprite"),
+        0xF000 => match (x, lb) {
+            (0x0, 0x00) => (Platform::XoChip, "long i := nnnn"),
+            (0x0, 0x02) => (Platform::XoChip, "audio pattern"),
+            (_, 0x01) => (Platform::XoChip, "plane select"),
+            (_, 0x30) => (Platform::SuperChip, "big font"),
+            (_, 0x3A) => (Platform::XoChip, "pitch"),
+            (_, 0x75) => (Platform::SuperChip, "save flags"),
+            (_, 0x85) => (Platform::SuperChip, "load flags"),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(evidence)
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relevant: Vec<_> = self
+            .evidence
+            .iter()
+            .filter(|e| e.platform == self.platform)
+            .collect();
+        if relevant.is_empty() {
+            return write!(f, "Detected {}: no platform specific opcodes found", self.platform);
+        }
+        write!(f, "Detected {}:", self.platform)?;
+        for e in relevant.iter().take(MAX_LOGGED) {
+            write!(f, "\n  {:04X} at 0x{:03X} ({})", e.op, e.addr, e.reason)?;
+        }
+        if relevant.len() > MAX_LOGGED {
+            write!(f, "\n  ...and {} more", relevant.len() - MAX_LOGGED)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(words: &[u16]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+
+    fn platform_of(ops: &[u16]) -> Option<Platform> {
+        let platforms: Vec<_> = ops.iter().map(|&op| classify(op).map(|(platform, _)| platform)).collect();
+        assert!(platforms.windows(2).all(|pair| pair[0] == pair[1]), "{:04X?} disagree", ops);
+        platforms[0]
+    }
+
+    #[test]
+    fn opcodes_are_classified() {
+        assert_eq!(platform_of(&[0x00FF, 0x00FE, 0x00FB, 0x00C4, 0xD120, 0xF330, 0xF575]), Some(Platform::SuperChip));
+        assert_eq!(platform_of(&[0xF000, 0x5122, 0x5123, 0x00D4, 0xF201, 0xF002, 0xF13A]), Some(Platform::XoChip));
+        assert_eq!(
+            platform_of(&[0x0000, 0x00E0, 0x00EE, 0x1234, 0x5120, 0x8124, 0xD125, 0xE19E, 0xF129, 0xF155, 0xF165]),
+            None
+        );
+    }
+
+    #[test]
+    fn schip_rom_is_detected() {
+        let analysis = analyze(&rom(&[0x00FF, 0x6005, 0x00FE, 0x00FD]));
+        assert_eq!(analysis.platform, Platform::SuperChip);
+        let found: Vec<_> = analysis.evidence.iter().map(|e| (e.addr, e.op)).collect();
+        assert_eq!(found, vec![(0x200, 0x00FF), (0x204, 0x00FE), (0x206, 0x00FD)]);
+    }
+
+    #[test]
+    fn xochip_rom_is_detected() {
+        //The skip has to jump the whole of the long load, or its address would be taken for hires
+        let analysis = analyze(&rom(&[0x3000, 0xF000, 0x00FF, 0x5122, 0x5123, 0x1208]));
+        assert_eq!(analysis.platform, Platform::XoChip);
+        let found: Vec<_> = analysis.evidence.iter().map(|e| e.op).collect();
+        assert_eq!(found.len(), 3);
+        assert!([0xF000, 0x5122, 0x5123].iter().all(|op| found.contains(op)));
+    }
+
+    #[test]
+    fn plain_rom_stays_chip8() {
+        let analysis = analyze(&rom(&[0x00E0, 0xA20A, 0x6000, 0xD005, 0x120A]));
+        assert_eq!(analysis.platform, Platform::Chip8);
+        assert!(analysis.evidence.is_empty());
+    }
+
+    #[test]
+    fn data_after_a_jump_is_not_run() {
+        //The sprite would read as hires and 16x16 draws if it were decoded
+        let analysis = analyze(&rom(&[0xA204, 0x1208, 0x00FF, 0xD120, 0xD015, 0x1208]));
+        assert_eq!(analysis.platform, Platform::Chip8);
+        assert!(analysis.evidence.is_empty());
+    }
+}
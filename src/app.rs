@@ -0,0 +1,595 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Error;
+
+use winit::{
+    event::{ElementState, Event, KeyEvent, MouseButton, Touch, TouchPhase, WindowEvent},
+    event_loop::{ControlFlow, EventLoopProxy, EventLoopWindowTarget},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Fullscreen, Window},
+};
+
+use crate::{
+    args::Args,
+    bindings::{Action, Bindings, Held, Hotkey},
+    chip8::event::Chip8Event,
+    chip8handler::Chip8Handler,
+    effects::Effects,
+    font,
+    gamepad::Gamepads,
+    hud::{Hud, ERROR_COLOR},
+    keypad::{VirtualKeypad, MOUSE_POINTER},
+    layout::{self, KeypadPosition, Rect, Rotation, ScaleMode},
+    palette::Palette,
+    preset,
+    profile::Profile,
+    recorder::{AudioRecorder, GifRecorder, VideoRecorder},
+    render::Renderer,
+    screenshot,
+};
+
+const GRID_ALPHA: f32 = 0.6;
+const VOLUME_STEP: f32 = 0.1;
+//How often gamepads are still polled while paused, they do not wake the event loop
+const PAUSED_POLL: Duration = Duration::from_millis(50);
+
+//The gamepads and the virtual keypad each count as one input, they track their own buttons and pointers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Source {
+    Keyboard(KeyCode),
+    Gamepad,
+    Keypad,
+}
+
+pub struct App<'a> {
+    window: &'a Window,
+    renderer: Renderer<'a>,
+    chip8: Chip8Handler,
+    bindings: Bindings<KeyCode>,
+    gamepads: Gamepads,
+    held: Held<Source>,
+    keypad: VirtualKeypad,
+    keypad_position: KeypadPosition,
+    scale: ScaleMode,
+    grid: bool,
+    rotation: Rotation,
+    palettes: Vec<Palette>,
+    palette_index: usize,
+    effects: Effects,
+    shader_dir: Option<PathBuf>,
+    shader: Option<String>,
+    shader_error: Option<String>,
+    screenshot_dir: PathBuf,
+    screenshot_scale: u32,
+    recording_dir: PathBuf,
+    recording_scale: u32,
+    gif: Option<(GifRecorder, PathBuf)>,
+    video: Option<(VideoRecorder, [PathBuf; 2])>,
+    audio: Option<(AudioRecorder, PathBuf)>,
+    hud: Hud,
+    pause_on_focus_loss: bool,
+    //Paused by losing focus rather than by the user, so focus coming back resumes
+    focus_paused: bool,
+    cursor: (f32, f32),
+    //Something changed on screen since the last redraw, which matters while paused
+    dirty: bool,
+}
+
+impl<'a> App<'a> {
+    pub async fn new(
+        window: &'a Window,
+        sys_tx: EventLoopProxy<Chip8Event>,
+        args: Args,
+        profile: Profile,
+    ) -> App<'a> {
+        let mut renderer = Renderer::new(window, args.software).await;
+        //No preset is loaded yet, so there is no shader chain that could fail to rebuild
+        renderer
+            .set_keypad(profile.show_keypad.then_some(profile.keypad_position))
+            .and(renderer.set_scale(profile.scale))
+            .and(renderer.set_rotation(profile.rotation))
+            .expect("Could not lay out the window");
+        renderer.set_palette(&profile.palettes[0]);
+        renderer.set_effects(&profile.effects);
+        let mut app = Self {
+            window,
+            renderer,
+            chip8: Chip8Handler::new(sys_tx, args.rom_path, profile.quirks, profile.tick_rate, profile.sound),
+            bindings: profile.bindings,
+            gamepads: Gamepads::new(profile.gamepad_bindings),
+            held: Held::default(),
+            keypad: VirtualKeypad::new(profile.show_keypad),
+            keypad_position: profile.keypad_position,
+            scale: profile.scale,
+            grid: profile.grid,
+            rotation: profile.rotation,
+            palettes: profile.palettes,
+            palette_index: 0,
+            effects: profile.effects,
+            shader_dir: profile.shader_dir,
+            shader: None,
+            shader_error: None,
+            screenshot_dir: profile.screenshot_dir,
+            screenshot_scale: profile.screenshot_scale,
+            recording_dir: profile.recording_dir,
+            recording_scale: profile.recording_scale,
+            gif: None,
+            video: None,
+            audio: None,
+            hud: Hud::new(profile.show_stats),
+            pause_on_focus_loss: profile.pause_on_focus_loss,
+            focus_paused: false,
+            cursor: (0.0, 0.0),
+            dirty: true,
+        };
+        if profile.shader.is_some() {
+            app.load_shader(profile.shader);
+        }
+        if let Some(path) = args.wav {
+            app.start_audio(path);
+        }
+        app
+    }
+
+    pub fn handle_event(
+        &mut self,
+        event: Event<Chip8Event>,
+        event_target: &EventLoopWindowTarget<Chip8Event>,
+    ) {
+        match event {
+            Event::UserEvent(Chip8Event::RequestRedraw) => {
+                self.window.request_redraw();
+            }
+            Event::AboutToWait => {
+                let actions = self.gamepads.poll();
+                self.dirty |= !actions.is_empty();
+                for (action, pressed) in actions {
+                    self.handle_input(Source::Gamepad, action, pressed, event_target);
+                }
+                //While paused the window is only drawn again when input or a toast changes it
+                if self.chip8.idle() && !self.dirty && !self.hud.animating() {
+                    event_target.set_control_flow(ControlFlow::WaitUntil(Instant::now() + PAUSED_POLL));
+                } else {
+                    event_target.set_control_flow(ControlFlow::Poll);
+                    self.window.request_redraw();
+                }
+            }
+            Event::WindowEvent { event, .. } => {
+                //Anything but the redraw itself may change what is on screen
+                self.dirty |= !matches!(event, WindowEvent::RedrawRequested);
+                match event {
+                    WindowEvent::CloseRequested => self.exit(event_target),
+                    WindowEvent::Resized(new_size) => {
+                        let result = self.renderer.resize(new_size);
+                        self.check_shader(result);
+                    }
+                    WindowEvent::RedrawRequested => self.redraw(),
+                    WindowEvent::Focused(false) if self.pause_on_focus_loss && !self.chip8.paused() => {
+                        self.focus_paused = true;
+                        self.set_paused(true);
+                    }
+                    WindowEvent::Focused(true) if self.focus_paused => {
+                        self.focus_paused = false;
+                        self.set_paused(false);
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(keycode),
+                                state,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } => {
+                        if let Some(action) = self.bindings.get(keycode) {
+                            let pressed = state == ElementState::Pressed;
+                            self.handle_input(Source::Keyboard(keycode), action, pressed, event_target);
+                        }
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        self.cursor = (position.x as f32, position.y as f32);
+                        self.handle_pointer(MOUSE_POINTER, TouchPhase::Moved);
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        let phase = match state {
+                            ElementState::Pressed => TouchPhase::Started,
+                            ElementState::Released => TouchPhase::Ended,
+                        };
+                        self.handle_pointer(MOUSE_POINTER, phase);
+                    }
+                    WindowEvent::Touch(Touch {
+                        phase, location, id, ..
+                    }) => {
+                        self.cursor = (location.x as f32, location.y as f32);
+                        self.handle_pointer(id, phase);
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn redraw(&mut self) {
+        self.dirty = false;
+        let instructions = self.chip8.update();
+        self.hud.frame(instructions.unwrap_or(0));
+        self.renderer
+            .update_screen(&self.chip8.get_frame_buffer().borrow());
+        //Recordings only get frames that were emulated
+        if instructions.is_some() {
+            self.record_frame();
+        }
+        let layout = self.renderer.layout();
+        let mut overlay = match layout.keypad {
+            Some(rect) => self.keypad.quads(rect, self.chip8.keypad_state()),
+            None => vec![],
+        };
+        if self.grid {
+            let [background, ..] = self.palettes[self.palette_index].to_rgba(true);
+            let [r, g, b, _] = background;
+            overlay.extend(layout::grid(layout.screen, self.rotation, [r, g, b, GRID_ALPHA]));
+        }
+        if let Some(error) = &self.shader_error {
+            let pixel = (layout.screen.w / 200.0).floor().max(1.0);
+            overlay.extend(font::text_box(layout.screen, error, pixel, ERROR_COLOR));
+        }
+        let size = self.window.inner_size();
+        let window = Rect::new(0.0, 0.0, size.width as f32, size.height as f32);
+        overlay.extend(self.hud.quads(window, layout.screen));
+        self.renderer.render(&overlay)
+    }
+
+    //Actions only happen when the first input bound to them goes down or the last one comes up
+    fn handle_input(
+        &mut self,
+        source: Source,
+        action: Action,
+        pressed: bool,
+        event_target: &EventLoopWindowTarget<Chip8Event>,
+    ) {
+        if self.held.update(source, action, pressed) {
+            self.handle_action(action, pressed, event_target);
+        }
+    }
+
+    fn handle_action(
+        &mut self,
+        action: Action,
+        pressed: bool,
+        event_target: &EventLoopWindowTarget<Chip8Event>,
+    ) {
+        match action {
+            Action::Keypad(key) => self.chip8.update_key(key, pressed),
+            Action::Hotkey(Hotkey::FastForward) => {
+                match pressed {
+                    true => self.chip8.start_ff(),
+                    false => self.chip8.stop_ff(),
+                }
+                self.hud.toast(format!("Speed: x{}", self.chip8.speed()));
+            }
+            Action::Hotkey(Hotkey::Pause) if pressed => {
+                self.focus_paused = false;
+                self.set_paused(!self.chip8.paused());
+            }
+            Action::Hotkey(Hotkey::FrameAdvance) if pressed => {
+                self.focus_paused = false;
+                self.chip8.advance_frame();
+                self.hud.paused = true;
+            }
+            Action::Hotkey(Hotkey::Reset) if pressed => {
+                self.chip8.reset();
+                self.hud.toast("Reset");
+            }
+            Action::Hotkey(Hotkey::VolumeUp) if pressed => self.change_volume(VOLUME_STEP),
+            Action::Hotkey(Hotkey::VolumeDown) if pressed => self.change_volume(-VOLUME_STEP),
+            Action::Hotkey(Hotkey::ToggleMute) if pressed => {
+                let muted = !self.chip8.sound().muted;
+                self.chip8.set_sound_muted(muted);
+                self.hud.toast(format!("Sound: {}", on_off(!muted)));
+            }
+            Action::Hotkey(Hotkey::Fullscreen) if pressed => match self.window.fullscreen() {
+                Some(_) => self.window.set_fullscreen(None),
+                None => self
+                    .window
+                    .set_fullscreen(Some(Fullscreen::Borderless(None))),
+            },
+            Action::Hotkey(Hotkey::ToggleKeypad) if pressed => self.toggle_keypad(),
+            Action::Hotkey(Hotkey::CycleTheme) if pressed => self.cycle_theme(),
+            Action::Hotkey(Hotkey::TogglePersistence) if pressed => {
+                self.effects.persistence = !self.effects.persistence;
+                self.notify(format!("Phosphor persistence: {}", on_off(self.effects.persistence)));
+                self.renderer.set_effects(&self.effects);
+            }
+            Action::Hotkey(Hotkey::ToggleCrt) if pressed => {
+                self.effects.crt = !self.effects.crt;
+                self.notify(format!("CRT effects: {}", on_off(self.effects.crt)));
+                self.renderer.set_effects(&self.effects);
+            }
+            Action::Hotkey(Hotkey::CycleScale) if pressed => {
+                self.scale = self.scale.next();
+                self.notify(format!("Scale: {}", self.scale));
+                let result = self.renderer.set_scale(self.scale);
+                self.check_shader(result);
+            }
+            Action::Hotkey(Hotkey::ToggleGrid) if pressed => {
+                self.grid = !self.grid;
+                self.notify(format!("Pixel grid: {}", on_off(self.grid)));
+            }
+            Action::Hotkey(Hotkey::ToggleStats) if pressed => self.hud.show_stats = !self.hud.show_stats,
+            Action::Hotkey(Hotkey::Screenshot) if pressed => {
+                let palette = &self.palettes[self.palette_index];
+                match self.chip8.save_screenshot(&self.screenshot_dir, palette, self.screenshot_scale) {
+                    Ok([raw, scaled]) => {
+                        println!("Saved {} and {}", raw.display(), scaled.display());
+                        self.hud.toast("Screenshot saved");
+                    }
+                    Err(e) => self.warn(format!("{:#}", e)),
+                }
+            }
+            Action::Hotkey(Hotkey::CycleShader) if pressed => self.cycle_shader(),
+            Action::Hotkey(Hotkey::ReloadShader) if pressed => self.load_shader(self.shader.clone()),
+            Action::Hotkey(Hotkey::RecordGif) if pressed => self.toggle_gif(),
+            Action::Hotkey(Hotkey::RecordVideo) if pressed => self.toggle_video(),
+            Action::Hotkey(Hotkey::RecordAudio) if pressed => self.toggle_audio(),
+            Action::Hotkey(Hotkey::Exit) => self.exit(event_target),
+            _ => (),
+        }
+    }
+
+    fn handle_pointer(&mut self, id: u64, phase: TouchPhase) {
+        let Some(rect) = self.renderer.layout().keypad else {
+            return;
+        };
+        let (x, y) = self.cursor;
+        let changes = match phase {
+            TouchPhase::Started => self.keypad.pointer_down(id, rect, x, y),
+            TouchPhase::Moved => self.keypad.pointer_moved(id, rect, x, y),
+            TouchPhase::Ended | TouchPhase::Cancelled => self.keypad.pointer_up(id),
+        };
+        for (key, pressed) in changes {
+            self.update_key(key, pressed);
+        }
+    }
+
+    //Keys from the virtual keypad, which has no hotkeys and so needs no event target
+    fn update_key(&mut self, key: u8, pressed: bool) {
+        if self.held.update(Source::Keypad, Action::Keypad(key), pressed) {
+            self.chip8.update_key(key, pressed);
+        }
+    }
+
+    fn cycle_theme(&mut self) {
+        self.palette_index = (self.palette_index + 1) % self.palettes.len();
+        let palette = &self.palettes[self.palette_index];
+        let message = format!("Theme: {}", palette.name);
+        self.renderer.set_palette(palette);
+        self.notify(message);
+    }
+
+    //Steps through the built-in shader and every preset currently in the directory
+    fn cycle_shader(&mut self) {
+        let Some(dir) = &self.shader_dir else {
+            self.notify("No shader directory set, use --shaders or [shaders] dir in the config".to_owned());
+            return;
+        };
+        let mut names = vec![None];
+        match preset::list(dir) {
+            Ok(presets) => names.extend(presets.into_iter().map(Some)),
+            Err(e) => self.warn(format!("{:#}", e)),
+        }
+        let next = match names.iter().position(|name| *name == self.shader) {
+            Some(i) => (i + 1) % names.len(),
+            None => 0,
+        };
+        self.load_shader(names.swap_remove(next));
+    }
+
+    //Failed presets stay selected so fixing the file and reloading picks them up
+    fn load_shader(&mut self, name: Option<String>) {
+        let result = match (&self.shader_dir, &name) {
+            (Some(dir), Some(name)) => preset::load(dir, name).and_then(|p| self.renderer.set_preset(Some(p))),
+            _ => self.renderer.set_preset(None),
+        };
+        let label = name.as_deref().unwrap_or("built-in");
+        match result {
+            Ok(()) => {
+                self.notify(format!("Shader: {}", label));
+                self.shader_error = None;
+            }
+            Err(e) => {
+                let message = format!("Shader {} failed, using the built-in one\n{:#}", label, e);
+                eprintln!("{}", message);
+                self.shader_error = Some(message);
+            }
+        }
+        self.shader = name;
+    }
+
+    //The renderer drops a preset that fails to rebuild for a new layout, so it is deselected too
+    fn check_shader(&mut self, result: Result<(), Error>) {
+        if let Err(e) = result {
+            let label = self.shader.take().unwrap_or_default();
+            let message = format!("Shader {} failed, using the built-in one\n{:#}", label, e);
+            eprintln!("{}", message);
+            self.shader_error = Some(message);
+        }
+    }
+
+    fn record_frame(&mut self) {
+        let buffer = self.chip8.get_frame_buffer();
+        let buffer = buffer.borrow();
+        if let Some((gif, _)) = &mut self.gif
+            && let Err(e) = gif.push(&buffer)
+        {
+            self.warn(format!("GIF recording stopped: {:#}", e));
+            self.gif = None;
+        }
+        let (start, edges) = self.chip8.frame_sound();
+        let edges = edges.to_vec();
+        if let Some((video, _)) = &mut self.video
+            && let Err(e) = video.push(&buffer, start, &edges)
+        {
+            self.warn(format!("Video recording stopped: {:#}", e));
+            self.video = None;
+        }
+        if let Some((audio, _)) = &mut self.audio
+            && let Err(e) = audio.push(start, &edges)
+        {
+            self.warn(format!("Audio recording stopped: {:#}", e));
+            self.audio = None;
+        }
+    }
+
+    fn recording_path(&self, ending: &str) -> PathBuf {
+        let timestamp = screenshot::timestamp(SystemTime::now());
+        screenshot::file_path(&self.recording_dir, self.chip8.rom_path(), &timestamp, ending)
+    }
+
+    fn toggle_gif(&mut self) {
+        if let Some((gif, path)) = self.gif.take() {
+            match gif.finish() {
+                Ok(()) => {
+                    println!("Saved {}", path.display());
+                    self.hud.toast("GIF saved");
+                }
+                Err(e) => self.warn(format!("Could not finish {}: {:#}", path.display(), e)),
+            }
+            return;
+        }
+        let path = self.recording_path(".gif");
+        let palette = &self.palettes[self.palette_index];
+        match GifRecorder::new(&path, palette, self.recording_scale as usize) {
+            Ok(gif) => {
+                println!("Recording {}", path.display());
+                self.hud.toast("Recording GIF");
+                self.gif = Some((gif, path));
+            }
+            Err(e) => self.warn(format!("{:#}", e)),
+        }
+    }
+
+    fn toggle_video(&mut self) {
+        if let Some((video, [video_path, audio_path])) = self.video.take() {
+            match video.finish() {
+                Ok(()) => {
+                    println!("Saved {} and {}", video_path.display(), audio_path.display());
+                    self.hud.toast("Video saved");
+                }
+                Err(e) => self.warn(format!("Could not finish {}: {:#}", video_path.display(), e)),
+            }
+            return;
+        }
+        let paths = [self.recording_path(".y4m"), self.recording_path(".wav")];
+        let video = VideoRecorder::new(
+            &paths[0],
+            &paths[1],
+            &self.palettes[self.palette_index],
+            self.recording_scale as usize,
+            self.chip8.sound(),
+            self.chip8.sound_playing(),
+        );
+        match video {
+            Ok(video) => {
+                println!("Recording {} and {}", paths[0].display(), paths[1].display());
+                self.hud.toast("Recording video");
+                self.video = Some((video, paths));
+            }
+            Err(e) => self.warn(format!("{:#}", e)),
+        }
+    }
+
+    fn toggle_audio(&mut self) {
+        if let Some((audio, path)) = self.audio.take() {
+            match audio.finish() {
+                Ok(()) => {
+                    println!("Saved {}", path.display());
+                    self.hud.toast("Audio saved");
+                }
+                Err(e) => self.warn(format!("Could not finish {}: {:#}", path.display(), e)),
+            }
+            return;
+        }
+        let path = self.recording_path(".wav");
+        self.start_audio(path);
+    }
+
+    fn start_audio(&mut self, path: PathBuf) {
+        match AudioRecorder::new(&path, self.chip8.sound(), self.chip8.sound_playing()) {
+            Ok(audio) => {
+                println!("Recording {}", path.display());
+                self.hud.toast("Recording audio");
+                self.audio = Some((audio, path));
+            }
+            Err(e) => self.warn(format!("{:#}", e)),
+        }
+    }
+
+    //Recordings still running are finished so the files are not left truncated
+    fn exit(&mut self, event_target: &EventLoopWindowTarget<Chip8Event>) {
+        if self.gif.is_some() {
+            self.toggle_gif();
+        }
+        if self.video.is_some() {
+            self.toggle_video();
+        }
+        if self.audio.is_some() {
+            self.toggle_audio();
+        }
+        event_target.exit();
+    }
+
+    //Rounded to whole steps so going up and back down lands where it started
+    fn change_volume(&mut self, step: f32) {
+        let volume = ((self.chip8.sound().volume + step) / VOLUME_STEP).round() * VOLUME_STEP;
+        self.chip8.set_volume(volume);
+        if self.chip8.sound().muted {
+            self.chip8.set_sound_muted(false);
+        }
+        self.hud.toast(format!("Volume: {:.0}%", self.chip8.sound().volume * 100.0));
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.chip8.set_paused(paused);
+        self.hud.paused = paused;
+    }
+
+    //Shown on screen as well as in the terminal
+    fn notify(&mut self, message: String) {
+        println!("{}", message);
+        self.hud.toast(message);
+    }
+
+    fn warn(&mut self, message: String) {
+        eprintln!("{}", message);
+        self.hud.error(message);
+    }
+
+    fn toggle_keypad(&mut self) {
+        self.keypad.visible = !self.keypad.visible;
+        if !self.keypad.visible {
+            for (key, pressed) in self.keypad.release_all() {
+                self.update_key(key, pressed);
+            }
+        }
+        let result = self
+            .renderer
+            .set_keypad(self.keypad.visible.then_some(self.keypad_position));
+        self.check_shader(result);
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    match enabled {
+        true => "on",
+        false => "off",
+    }
+}
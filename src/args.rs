@@ -0,0 +1,233 @@
+use std::{env, path::PathBuf};
+
+use anyhow::{anyhow, Context, Error};
+
+use crate::{
+    chip8::quirks::Platform,
+    headless::{self, ScheduledKey},
+    layout::Rotation,
+};
+
+const USAGE: &str = "Usage: chip8-emulator [run] [options] <path-to-rom>
+       chip8-emulator bench [--seconds <n>] [options] <path-to-rom>
+
+Options:
+  --platform <chip8|schip|xochip>  Override the detected platform
+  --tickrate <n>                   Instructions executed per frame
+  --quirk <name>=<true|false>      Override a single quirk, may be repeated
+  --title <title>                  Window title
+  --rotation <0|90|180|270>        Turn the screen clockwise for games made for a rotated display
+  --database <path>                Use a chip-8-database programs.json
+  --config <path>                  Read settings from this file instead of chip8.toml
+  --shaders <dir>                  Directory of WGSL shader presets
+  --shader <name>                  Start with this preset from the shader directory
+  --software                       Draw on the CPU even when a GPU is available
+  --wav <path>                     Record the beeper to a WAV file from the first frame
+
+Headless options, for running without a window, GPU or sound:
+  --headless                       Run a fixed number of frames and print the final screen
+  --frames <n>                     Frames to run, required with --headless
+  --output <path>                  Save the final screen as .png or .pbm instead of printing it
+  --keys <schedule>                Keys pressed (+) and released (-) at the start of a frame,
+                                   e.g. \"30+5,40-5\", or @path to read them from a file
+  --differential                   Also run the simple reference interpreter in lock-step and
+                                   stop at the first register, RAM or screen difference
+
+Benchmark options, for bench, which runs the rom uncapped and prints instructions per second:
+  --seconds <n>                    How long to time the rom and each opcode class, 1 by default";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub rom_path: PathBuf,
+    pub platform: Option<Platform>,
+    pub tick_rate: Option<u32>,
+    pub quirks: Vec<(String, bool)>,
+    pub title: Option<String>,
+    pub rotation: Option<Rotation>,
+    pub database: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub shader_dir: Option<PathBuf>,
+    pub shader: Option<String>,
+    pub software: bool,
+    pub wav: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub output: Option<PathBuf>,
+    pub keys: Vec<ScheduledKey>,
+    pub differential: bool,
+    pub bench: bool,
+    pub seconds: Option<f64>,
+}
+
+impl Args {
+    pub fn from_env() -> Result<Args, Error> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, Error> {
+        let mut out = Args::default();
+        let mut rom_path = None;
+        let mut args = args.into_iter().peekable();
+        //"run" is optional, plain options start the emulator too
+        if let Some(command) = args.next_if(|arg| arg == "run" || arg == "bench") {
+            out.bench = command == "bench";
+        }
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for {}\n\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--platform" => out.platform = Some(value()?.parse()?),
+                "--tickrate" => {
+                    let tick_rate: u32 = value()?.parse().context("Tickrate must be a number")?;
+                    if tick_rate == 0 {
+                        return Err(anyhow!("Tickrate must be more than 0"));
+                    }
+                    out.tick_rate = Some(tick_rate);
+                }
+                "--quirk" => {
+                    let value = value()?;
+                    let (name, state) = value
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("Quirks are set with <name>=<true|false>"))?;
+                    let state = state.parse().context("Quirks are set with <name>=<true|false>")?;
+                    out.quirks.push((name.to_owned(), state));
+                }
+                "--title" => out.title = Some(value()?),
+                "--rotation" => {
+                    let degrees = value()?.parse::<u32>().context("Rotation must be a number of degrees")?;
+                    out.rotation = Some(Rotation::try_from(degrees).map_err(|e| anyhow!(e))?);
+                }
+                "--database" => out.database = Some(value()?.into()),
+                "--config" => out.config = Some(value()?.into()),
+                "--shaders" => out.shader_dir = Some(value()?.into()),
+                "--shader" => out.shader = Some(value()?),
+                "--software" => out.software = true,
+                "--wav" => out.wav = Some(value()?.into()),
+                "--headless" => out.headless = true,
+                "--frames" => out.frames = Some(value()?.parse().context("Frames must be a number")?),
+                "--output" => out.output = Some(value()?.into()),
+                "--keys" => out.keys.extend(headless::parse_schedule(&value()?)?),
+                "--differential" => out.differential = true,
+                "--seconds" => {
+                    let seconds: f64 = value()?.parse().context("Seconds must be a number")?;
+                    if !(seconds > 0.0 && seconds.is_finite()) {
+                        return Err(anyhow!("Seconds must be more than 0"));
+                    }
+                    out.seconds = Some(seconds);
+                }
+                "-h" | "--help" => return Err(anyhow!(USAGE)),
+                _ if arg.starts_with("--") => return Err(anyhow!("Unknown option {}\n\n{}", arg, USAGE)),
+                _ => rom_path = Some(PathBuf::from(arg)),
+            }
+        }
+        out.rom_path = rom_path.ok_or_else(|| anyhow!("Please provide a path to rom\n\n{}", USAGE))?;
+        if out.bench && !cfg!(feature = "bench") {
+            return Err(anyhow!("bench needs the emulator built with --features bench"));
+        }
+        if out.bench && out.headless {
+            return Err(anyhow!("bench runs without a window already, leave out --headless\n\n{}", USAGE));
+        }
+        if out.seconds.is_some() && !out.bench {
+            return Err(anyhow!("--seconds only works with bench\n\n{}", USAGE));
+        }
+        if out.headless {
+            if out.frames.is_none() {
+                return Err(anyhow!("--headless needs --frames\n\n{}", USAGE));
+            }
+            if let Some(path) = &out.output {
+                headless::check_output(path)?;
+            }
+        } else if out.frames.is_some() || out.output.is_some() || !out.keys.is_empty() || out.differential {
+            return Err(anyhow!(
+                "--frames, --output, --keys and --differential only work with --headless\n\n{}",
+                USAGE
+            ));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, Error> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn run_is_optional() {
+        assert_eq!(parse(&["rom.ch8"]).unwrap().rom_path, PathBuf::from("rom.ch8"));
+        assert_eq!(parse(&["run", "rom.ch8"]).unwrap().rom_path, PathBuf::from("rom.ch8"));
+        assert!(!parse(&["run", "rom.ch8"]).unwrap().bench);
+        assert!(parse(&["run"]).is_err());
+    }
+
+    #[test]
+    fn headless_needs_frames() {
+        assert!(parse(&["run", "--headless", "rom.ch8"]).is_err());
+        let args = parse(&["run", "--headless", "--frames", "600", "--keys", "30+5", "--keys", "40-5", "rom.ch8"]).unwrap();
+        assert!(args.headless);
+        assert_eq!(args.frames, Some(600));
+        assert_eq!(args.keys.len(), 2);
+        assert!(parse(&["--headless", "--frames", "lots", "rom.ch8"]).is_err());
+    }
+
+    #[test]
+    fn headless_options_need_headless() {
+        for options in [
+            &["--frames", "10"][..],
+            &["--output", "end.png"],
+            &["--keys", "30+5"],
+            &["--differential"],
+        ] {
+            let mut args = options.to_vec();
+            args.push("rom.ch8");
+            assert!(parse(&args).is_err(), "{:?} was accepted", options);
+        }
+    }
+
+    #[test]
+    fn headless_output_is_checked_up_front() {
+        assert!(parse(&["--headless", "--frames", "1", "--output", "end.pbm", "rom.ch8"]).is_ok());
+        assert!(parse(&["--headless", "--frames", "1", "--output", "end.gif", "rom.ch8"]).is_err());
+    }
+
+    #[test]
+    fn headless_can_record_audio() {
+        let args = parse(&["--headless", "--frames", "1", "--wav", "beep.wav", "rom.ch8"]).unwrap();
+        assert_eq!(args.wav, Some(PathBuf::from("beep.wav")));
+    }
+
+    #[test]
+    #[cfg(feature = "bench")]
+    fn bench_takes_seconds() {
+        let args = parse(&["bench", "--seconds", "0.5", "rom.ch8"]).unwrap();
+        assert!(args.bench);
+        assert_eq!(args.seconds, Some(0.5));
+        assert!(parse(&["bench", "--seconds", "0", "rom.ch8"]).is_err());
+        assert!(parse(&["--seconds", "1", "rom.ch8"]).is_err());
+        assert!(parse(&["bench", "--headless", "--frames", "1", "rom.ch8"]).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "bench"))]
+    fn bench_needs_its_feature() {
+        assert!(parse(&["bench", "rom.ch8"]).is_err());
+    }
+
+    #[test]
+    fn unknown_options_are_errors() {
+        assert!(parse(&["--speed", "rom.ch8"]).is_err());
+        assert!(parse(&["--tickrate"]).is_err());
+    }
+
+    #[test]
+    fn tickrate_is_positive() {
+        assert_eq!(parse(&["--tickrate", "30", "rom.ch8"]).unwrap().tick_rate, Some(30));
+        assert!(parse(&["--tickrate", "0", "rom.ch8"]).is_err());
+        assert!(parse(&["--tickrate", "-1", "rom.ch8"]).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::Args,
+    chip8::Chip8,
+    profile::Profile,
+    workloads::{self, CLASSES},
+};
+
+const DEFAULT_TIME: Duration = Duration::from_secs(1);
+//Instructions run between looks at the clock
+const CHUNK: u64 = 10_000;
+//Instructions decoded to find how the rom splits across opcode classes
+const MIX_INSTRUCTIONS: u64 = 1_000_000;
+
+//Runs the rom uncapped with no window, GPU or sound and prints its instructions per second,
+//then the rom's share of each opcode class next to how fast that class runs on its own
+pub fn run(args: &Args, profile: &Profile, rom: &[u8]) {
+    let time = args.seconds.map_or(DEFAULT_TIME, Duration::from_secs_f64);
+    let mut cpu = Chip8::new().with_quirks(profile.quirks).with_rom(rom).with_seed(0);
+    let (speed, instructions) = measure(&mut cpu, profile.tick_rate, time);
+    println!(
+        "{:.2}M instructions per second, {:.0} times the speed of {} per frame at 60 Hz",
+        speed / 1e6,
+        speed / (profile.tick_rate as f64 * 60.0),
+        profile.tick_rate
+    );
+    //Only what ran before the rom stopped is timed
+    if cpu.halted() {
+        println!("The rom stopped to wait for a key after {} instructions", instructions);
+    }
+    if let Some(e) = cpu.error() {
+        println!("The rom stopped on a CPU error after {} instructions\n{}", instructions, e);
+    }
+
+    let mix = mix(rom, profile);
+    let total = mix.values().sum::<u64>().max(1);
+    let workloads = workloads::workloads();
+    println!("\n{:<8}{:>8}{:>14}", "Class", "Of rom", "Per second");
+    for class in CLASSES {
+        let share = mix.get(class).copied().unwrap_or(0) as f64 / total as f64;
+        let speed = match workloads.iter().find(|w| w.class == class) {
+            Some(workload) => format!("{:.2}M", measure(&mut workload.cpu(), profile.tick_rate, time).0 / 1e6),
+            None => "-".to_owned(),
+        };
+        println!("{:<8}{:>7.1}%{:>14}", class, share * 100.0, speed);
+    }
+}
+
+//Instructions per second and how many ran, which ends early if the rom stops
+fn measure(cpu: &mut Chip8, ipf: u32, time: Duration) -> (f64, u64) {
+    let start = Instant::now();
+    let mut instructions = 0;
+    while start.elapsed() < time {
+        let ran = workloads::run(cpu, CHUNK, ipf);
+        instructions += ran;
+        if ran < CHUNK {
+            break;
+        }
+    }
+    (instructions as f64 / start.elapsed().as_secs_f64(), instructions)
+}
+
+//Kept apart from the timed run, since decoding every instruction twice would slow it down.
+//Like the timed run it stops with the rom
+fn mix(rom: &[u8], profile: &Profile) -> HashMap<&'static str, u64> {
+    let mut cpu = Chip8::new().with_quirks(profile.quirks).with_rom(rom).with_seed(0);
+    let mut mix = HashMap::new();
+    for n in 1..=MIX_INSTRUCTIONS {
+        if cpu.halted() || cpu.error().is_some() {
+            break;
+        }
+        let class = cpu.opcode().map_or("other", workloads::class);
+        *mix.entry(class).or_insert(0) += 1;
+        cpu.update();
+        if n % profile.tick_rate as u64 == 0 {
+            cpu.update_timers();
+        }
+    }
+    mix
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::Hash,
+};
+
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use winit::keyboard::KeyCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Keypad(u8),
+    Hotkey(Hotkey),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hotkey {
+    FastForward,
+    Pause,
+    FrameAdvance,
+    Reset,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    Fullscreen,
+    ToggleKeypad,
+    CycleTheme,
+    TogglePersistence,
+    ToggleCrt,
+    CycleShader,
+    ReloadShader,
+    CycleScale,
+    ToggleGrid,
+    ToggleStats,
+    Screenshot,
+    RecordGif,
+    RecordVideo,
+    RecordAudio,
+    Exit,
+}
+
+//QWERTY layout of the COSMAC VIP keypad
+const DEFAULT_KEYPAD: [(KeyCode, u8); 16] = [
+    (KeyCode::Digit1, 0x1),
+    (KeyCode::Digit2, 0x2),
+    (KeyCode::Digit3, 0x3),
+    (KeyCode::Digit4, 0xC),
+    (KeyCode::KeyQ, 0x4),
+    (KeyCode::KeyW, 0x5),
+    (KeyCode::KeyE, 0x6),
+    (KeyCode::KeyR, 0xD),
+    (KeyCode::KeyA, 0x7),
+    (KeyCode::KeyS, 0x8),
+    (KeyCode::KeyD, 0x9),
+    (KeyCode::KeyF, 0xE),
+    (KeyCode::KeyZ, 0xA),
+    (KeyCode::KeyX, 0x0),
+    (KeyCode::KeyC, 0xB),
+    (KeyCode::KeyV, 0xF),
+];
+
+const DEFAULT_HOTKEYS: [(KeyCode, Hotkey); 22] = [
+    (KeyCode::Space, Hotkey::FastForward),
+    (KeyCode::KeyP, Hotkey::Pause),
+    (KeyCode::KeyN, Hotkey::FrameAdvance),
+    (KeyCode::Backslash, Hotkey::Reset),
+    (KeyCode::Equal, Hotkey::VolumeUp),
+    (KeyCode::Minus, Hotkey::VolumeDown),
+    (KeyCode::KeyM, Hotkey::ToggleMute),
+    (KeyCode::Enter, Hotkey::Fullscreen),
+    (KeyCode::F1, Hotkey::ToggleKeypad),
+    (KeyCode::F2, Hotkey::CycleTheme),
+    (KeyCode::F3, Hotkey::TogglePersistence),
+    (KeyCode::F4, Hotkey::ToggleCrt),
+    (KeyCode::F5, Hotkey::CycleShader),
+    (KeyCode::F6, Hotkey::ReloadShader),
+    (KeyCode::F7, Hotkey::CycleScale),
+    (KeyCode::F8, Hotkey::ToggleGrid),
+    (KeyCode::F9, Hotkey::RecordGif),
+    (KeyCode::F10, Hotkey::RecordVideo),
+    (KeyCode::KeyB, Hotkey::RecordAudio),
+    (KeyCode::F11, Hotkey::ToggleStats),
+    (KeyCode::F12, Hotkey::Screenshot),
+    (KeyCode::Escape, Hotkey::Exit),
+];
+
+//Every physical input, keyboard key or gamepad button, triggers at most one action
+#[derive(Clone, Debug)]
+pub struct Bindings<K> {
+    inputs: HashMap<K, Action>,
+}
+
+impl<K: Copy + Eq + Hash> Bindings<K> {
+    pub fn new(keypad: &[(K, u8)], hotkeys: &[(K, Hotkey)]) -> Self {
+        let keypad = keypad
+            .iter()
+            .map(|&(input, key)| (input, Action::Keypad(key)));
+        let hotkeys = hotkeys
+            .iter()
+            .map(|&(input, hotkey)| (input, Action::Hotkey(hotkey)));
+        Self {
+            inputs: keypad.chain(hotkeys).collect(),
+        }
+    }
+
+    pub fn get(&self, input: K) -> Option<Action> {
+        self.inputs.get(&input).copied()
+    }
+
+    //Binds an extra input without touching the existing bindings of the action
+    pub fn add(&mut self, input: K, action: Action) {
+        self.inputs.insert(input, action);
+    }
+
+    //Each action listed in the config replaces all previous inputs bound to it
+    pub fn apply(
+        &mut self,
+        keys: &BTreeMap<String, Vec<K>>,
+        hotkeys: &BTreeMap<Hotkey, Vec<K>>,
+    ) -> Result<(), Error> {
+        for (name, inputs) in keys {
+            self.replace(Action::Keypad(parse_keypad_key(name)?), inputs);
+        }
+        for (&hotkey, inputs) in hotkeys {
+            self.replace(Action::Hotkey(hotkey), inputs);
+        }
+        Ok(())
+    }
+
+    fn replace(&mut self, action: Action, inputs: &[K]) {
+        self.inputs.retain(|_, a| *a != action);
+        inputs.iter().for_each(|&input| self.add(input, action));
+    }
+}
+
+//Which inputs are holding each action. An action bound to several inputs goes down with the
+//first of them and only comes up when the last one is let go
+pub struct Held<S> {
+    sources: HashMap<Action, HashSet<S>>,
+}
+
+impl<S> Default for Held<S> {
+    fn default() -> Self {
+        Self { sources: HashMap::new() }
+    }
+}
+
+impl<S: Eq + Hash> Held<S> {
+    //Returns whether the action itself went down or up
+    pub fn update(&mut self, source: S, action: Action, pressed: bool) -> bool {
+        let sources = self.sources.entry(action).or_default();
+        let was_held = !sources.is_empty();
+        match pressed {
+            true => sources.insert(source),
+            false => sources.remove(&source),
+        };
+        was_held == sources.is_empty()
+    }
+}
+
+impl Default for Bindings<KeyCode> {
+    fn default() -> Self {
+        Self::new(&DEFAULT_KEYPAD, &DEFAULT_HOTKEYS)
+    }
+}
+
+pub fn parse_keypad_key(name: &str) -> Result<u8, Error> {
+    u8::from_str_radix(name, 16)
+        .ok()
+        .filter(|&key| key < 0x10)
+        .ok_or_else(|| anyhow!("\"{}\" is not a Chip-8 key, expected 0-F", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_binds_several_keys() {
+        let mut bindings = Bindings::default();
+        let keys = BTreeMap::from([("5".to_owned(), vec![KeyCode::KeyW, KeyCode::ArrowUp])]);
+        bindings.apply(&keys, &BTreeMap::new()).unwrap();
+        assert_eq!(bindings.get(KeyCode::KeyW), Some(Action::Keypad(0x5)));
+        assert_eq!(bindings.get(KeyCode::ArrowUp), Some(Action::Keypad(0x5)));
+        assert_eq!(bindings.get(KeyCode::KeyQ), Some(Action::Keypad(0x4)));
+        assert!(bindings.apply(&BTreeMap::from([("G".to_owned(), vec![])]), &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn later_configs_replace_an_action() {
+        //The user config first, then the rom's own, which only rebinds 5
+        let mut bindings = Bindings::default();
+        let user = BTreeMap::from([
+            ("5".to_owned(), vec![KeyCode::KeyW, KeyCode::ArrowUp]),
+            ("8".to_owned(), vec![KeyCode::ArrowDown]),
+        ]);
+        let rom = BTreeMap::from([("5".to_owned(), vec![KeyCode::KeyI])]);
+        let hotkeys = BTreeMap::from([(Hotkey::Pause, vec![KeyCode::KeyP, KeyCode::Pause])]);
+        bindings.apply(&user, &hotkeys).unwrap();
+        bindings.apply(&rom, &BTreeMap::new()).unwrap();
+        assert_eq!(bindings.get(KeyCode::KeyI), Some(Action::Keypad(0x5)));
+        assert_eq!(bindings.get(KeyCode::KeyW), None);
+        assert_eq!(bindings.get(KeyCode::ArrowUp), None);
+        assert_eq!(bindings.get(KeyCode::ArrowDown), Some(Action::Keypad(0x8)));
+        assert_eq!(bindings.get(KeyCode::Pause), Some(Action::Hotkey(Hotkey::Pause)));
+    }
+
+    #[test]
+    fn key_held_by_two_inputs() {
+        let mut held = Held::default();
+        let key = Action::Keypad(0x5);
+        assert!(held.update(KeyCode::KeyW, key, true));
+        assert!(!held.update(KeyCode::ArrowUp, key, true));
+        assert!(!held.update(KeyCode::KeyW, key, false));
+        assert!(held.update(KeyCode::ArrowUp, key, false));
+    }
+
+    #[test]
+    fn repeated_and_unmatched_inputs_are_ignored() {
+        let mut held = Held::default();
+        let key = Action::Keypad(0x5);
+        //A release that was never pressed, from a key already down when the window got focus
+        assert!(!held.update(KeyCode::KeyW, key, false));
+        assert!(held.update(KeyCode::KeyW, key, true));
+        assert!(!held.update(KeyCode::KeyW, key, true));
+        assert!(held.update(KeyCode::KeyW, key, false));
+        //Other actions are tracked on their own
+        assert!(held.update(KeyCode::KeyW, Action::Keypad(0x6), true));
+    }
+}
@@ -0,0 +1,289 @@
+use rodio::{OutputStream, Sink, Source};
+use rtrb::{Consumer, Producer, RingBuffer};
+use serde::Deserialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self},
+    time::Duration,
+};
+
+use std::f32::consts::TAU;
+
+pub const SAMPLE_RATE: u32 = 44100;
+//Emulated time is counted in samples, one timer tick lasts exactly this many
+pub const SAMPLES_PER_TICK: u64 = SAMPLE_RATE as u64 / 60;
+//How far the speaker stays behind the emulator, so edges arrive before they are due
+const LATENCY: u64 = SAMPLES_PER_TICK * 2;
+//Falling further behind than this, after a stall or fast-forward, skips ahead
+const MAX_LAG: u64 = SAMPLES_PER_TICK * 6;
+const QUEUE_SIZE: usize = 1024;
+//Ramping the level instead of cutting it removes the click of stopping mid-cycle
+const ATTACK: Duration = Duration::from_millis(2);
+const RELEASE: Duration = Duration::from_millis(10);
+//The noise picks a new level this many times per period of the tone
+const NOISE_STEPS: f32 = 16.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Waveform {
+    #[default]
+    Square,
+    //Square with the high part lasting duty of the period
+    Pulse,
+    Triangle,
+    Sine,
+    Noise,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub duty: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 261.60,
+            duty: 0.25,
+        }
+    }
+}
+
+//What the buzzer sounds like and how loud, volume goes from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sound {
+    pub tone: Tone,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for Sound {
+    fn default() -> Self {
+        Self {
+            tone: Tone::default(),
+            volume: 0.2,
+            muted: false,
+        }
+    }
+}
+
+//The buzzer turning on or off at a point in emulated time
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub at: u64,
+    pub on: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum AudioEvent {
+    Edge(Edge),
+    Muted(bool),
+    Volume(f32),
+}
+
+//Speaker fed by the emulator through a lock-free queue, so short beeps last exactly as long as the sound timer
+pub struct Buzzer {
+    events: Producer<AudioEvent>,
+    //Emulated time the emulator has reached, the speaker plays a little behind it
+    clock: Arc<AtomicU64>,
+}
+
+impl Buzzer {
+    pub fn new(sound: Sound) -> Self {
+        let (events, consumer) = RingBuffer::new(QUEUE_SIZE);
+        let clock = Arc::new(AtomicU64::new(0));
+        let voice = Voice::new(consumer, clock.clone(), sound);
+        thread::spawn(move || Self::event_handler(voice));
+        Self { events, clock }
+    }
+
+    pub fn push(&mut self, edge: Edge) {
+        #[cfg(feature = "sound_debug")]
+        println!("Sound {} at sample {}", if edge.on { "start" } else { "stop" }, edge.at);
+        self.send(AudioEvent::Edge(edge));
+    }
+
+    //Moves emulated time forward, called once per timer tick
+    pub fn advance(&mut self, now: u64) {
+        self.clock.store(now, Ordering::Release);
+    }
+
+    //Silences the speaker without the program noticing, a tone still playing resumes on unmute
+    pub fn set_muted(&mut self, muted: bool) {
+        self.send(AudioEvent::Muted(muted));
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.send(AudioEvent::Volume(volume));
+    }
+
+    //A full queue means the speaker has stopped listening, the Chip-8 carries on either way
+    fn send(&mut self, event: AudioEvent) {
+        let _ = self.events.push(event);
+    }
+
+    //Keeps the output stream alive until the voice ends, which it does once the buzzer is dropped
+    fn event_handler(voice: Voice) {
+        let Ok((_stream, stream_handle)) = OutputStream::try_default() else {
+            eprintln!("No audio output device, sound is disabled");
+            return;
+        };
+        let sink = Sink::try_new(&stream_handle).unwrap();
+        sink.append(voice);
+        sink.sleep_until_end();
+    }
+}
+
+//Waveform generator with an attack and release so starting and stopping does not click
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    tone: Tone,
+    sample_rate: u32,
+    //Position in the current period from 0 to 1
+    phase: f32,
+    level: f32,
+    noise: u16,
+    noise_step: u32,
+}
+
+impl Oscillator {
+    pub fn new(tone: Tone, sample_rate: u32) -> Self {
+        Self {
+            tone,
+            sample_rate,
+            phase: 0.0,
+            level: 0.0,
+            noise: 0xACE1,
+            noise_step: 0,
+        }
+    }
+
+    //Next sample from -1 to 1, rising while the gate is open and dying away after it closes
+    pub fn next_sample(&mut self, gate: bool) -> f32 {
+        let rate = self.sample_rate as f32;
+        self.level = match gate {
+            true => (self.level + 1.0 / (ATTACK.as_secs_f32() * rate)).min(1.0),
+            false => (self.level - 1.0 / (RELEASE.as_secs_f32() * rate)).max(0.0),
+        };
+        if self.level == 0.0 {
+            //Every beep starts from the same point of the wave
+            self.phase = 0.0;
+            return 0.0;
+        }
+
+        let phase = self.phase;
+        let value = match self.tone.waveform {
+            Waveform::Square => match phase < 0.5 {
+                true => 1.0,
+                false => -1.0,
+            },
+            Waveform::Pulse => match phase < self.tone.duty {
+                true => 1.0,
+                false => -1.0,
+            },
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sine => (TAU * phase).sin(),
+            Waveform::Noise => {
+                let step = (phase * NOISE_STEPS) as u32;
+                if step != self.noise_step {
+                    self.noise_step = step;
+                    //16 bit Galois LFSR, the same hiss every time
+                    let bit = self.noise & 1;
+                    self.noise >>= 1;
+                    if bit == 1 {
+                        self.noise ^= 0xB400;
+                    }
+                }
+                match self.noise & 1 {
+                    1 => 1.0,
+                    _ => -1.0,
+                }
+            }
+        };
+        self.phase = (self.phase + self.tone.frequency / rate).fract();
+        value * self.level
+    }
+}
+
+//Plays for as long as the buzzer exists, applying each edge at its sample
+struct Voice {
+    events: Consumer<AudioEvent>,
+    clock: Arc<AtomicU64>,
+    //Emulated time of the next sample
+    time: u64,
+    oscillator: Oscillator,
+    gate: bool,
+    muted: bool,
+    volume: f32,
+}
+
+impl Voice {
+    fn new(events: Consumer<AudioEvent>, clock: Arc<AtomicU64>, sound: Sound) -> Self {
+        Self {
+            events,
+            clock,
+            time: 0,
+            oscillator: Oscillator::new(sound.tone, SAMPLE_RATE),
+            gate: false,
+            muted: sound.muted,
+            volume: sound.volume,
+        }
+    }
+}
+
+impl Iterator for Voice {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let now = self.clock.load(Ordering::Acquire);
+        if now > self.time + MAX_LAG {
+            self.time = now - LATENCY;
+        }
+        //Events are taken in order, each edge waiting until its time comes
+        while let Ok(event) = self.events.peek() {
+            if let AudioEvent::Edge(edge) = event
+                && edge.at > self.time
+            {
+                break;
+            }
+            match self.events.pop() {
+                Ok(AudioEvent::Edge(edge)) => self.gate = edge.on,
+                Ok(AudioEvent::Muted(muted)) => self.muted = muted,
+                Ok(AudioEvent::Volume(volume)) => self.volume = volume,
+                Err(_) => break,
+            }
+        }
+        if self.events.is_abandoned() && self.events.is_empty() {
+            return None;
+        }
+        //Time stands still while the emulator is paused or stalled
+        if self.time < now {
+            self.time += 1;
+        }
+        Some(self.oscillator.next_sample(self.gate && !self.muted) * self.volume)
+    }
+}
+
+impl Source for Voice {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -1,24 +1,28 @@
-mod buzzer;
-pub(crate) mod event;
-mod keyboard;
-pub(crate) mod screen;
+pub mod event;
+pub mod keyboard;
+pub mod quirks;
+pub mod screen;
 mod stack;
+#[cfg(test)]
+mod tests;
 
-use self::buzzer::Buzzer;
-use self::keyboard::Keyboard;
+use self::keyboard::{Keyboard, KeypadState};
+use self::quirks::Quirks;
 use self::screen::{Screen, ScreenBuffer};
 use self::stack::Stack;
 use crate::chip8::event::Chip8Event;
 use anyhow::Error;
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::fmt;
 #[cfg(debug_assertions)]
 use std::fmt::Write;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CPUError {
     UnknownOpcode(u16, u16),
     RamOutOfBounds,
+    StackOverflow,
+    StackUnderflow,
 }
 
 impl fmt::Display for CPUError {
@@ -29,6 +33,8 @@ impl fmt::Display for CPUError {
                 i, pc
             ),
             CPUError::RamOutOfBounds => write!(f, "ERROR: Ran out of RAM"),
+            CPUError::StackOverflow => write!(f, "ERROR: Too many nested calls, the stack holds 16"),
+            CPUError::StackUnderflow => write!(f, "ERROR: Returned without a call"),
         }
     }
 }
@@ -39,16 +45,40 @@ pub const ERR_LOC: u16 = (TEXT_SPRITES.len() * 5) as u16;
 
 pub struct Chip8 {
     screen: Screen,
-    buzzer: Buzzer,
+    //Whether the buzzer should be sounding, the frontend decides how that is heard
+    sound: bool,
     kb: Keyboard,
     stack: Stack,
+    quirks: Quirks,
     kb_halt_reg: Option<usize>,
+    //The first error the program ran into, it carries on from the error screen after
+    error: Option<CPUError>,
     ram: [u8; 0x1000],
     reg: [u8; 16],
     dt: u8,
     st: u8,
     i: u16,
     pc: u16,
+    rng: StdRng,
+}
+
+//Everything a program can see or change, for comparing the core with another interpreter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub i: u16,
+    pub reg: [u8; 16],
+    //Return addresses, innermost call last
+    pub stack: Vec<u16>,
+    pub dt: u8,
+    pub st: u8,
+    pub sound: bool,
+    //Register waiting for a key from Fx0A
+    pub halted: Option<usize>,
+    pub error: Option<CPUError>,
+    pub ram: Vec<u8>,
+    //Packed like the display buffer, 8 pixels to a byte
+    pub screen: Vec<u8>,
 }
 
 impl Chip8 {
@@ -67,23 +97,39 @@ impl Chip8 {
 
         Chip8 {
             screen: Screen::default(),
-            buzzer: Buzzer::new(),
+            sound: false,
             stack: Stack::default(),
             kb: Keyboard::default(),
+            quirks: Quirks::default(),
             ram,
             reg: [0x00; 16],
             kb_halt_reg: None,
+            error: None,
             dt: 0,
             st: 0,
             i: 0,
             pc: ENTRY_POINT,
+            rng: StdRng::from_entropy(),
         }
     }
 
+    //Anything past the end of RAM is left out
     pub fn with_rom(mut self, bytes: &[u8]) -> Self {
-        bytes.iter().enumerate().for_each(|(i, &b)| {
-            self.ram[ENTRY_POINT as usize + i] = b;
-        });
+        self.ram[ENTRY_POINT as usize..]
+            .iter_mut()
+            .zip(bytes)
+            .for_each(|(ram, &b)| *ram = b);
+        self
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    //Makes Cxnn draw the same numbers on every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
         self
     }
 
@@ -91,17 +137,93 @@ impl Chip8 {
         self.screen.extract_buffer()
     }
 
+    pub fn keypad_state(&self) -> KeypadState {
+        self.kb.state()
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.sound
+    }
+
+    pub fn error(&self) -> Option<CPUError> {
+        self.error
+    }
+
+    //Waiting on Fx0A, when updates run nothing until a key is let go
+    pub fn halted(&self) -> bool {
+        self.kb_halt_reg.is_some()
+    }
+
+    //The instruction at PC, what the next update runs unless halted
+    pub fn opcode(&self) -> Option<u16> {
+        let pc = self.pc as usize;
+        Some(u16::from_be_bytes([*self.ram.get(pc)?, *self.ram.get(pc + 1)?]))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pc: self.pc,
+            i: self.i,
+            reg: self.reg,
+            stack: self.stack.entries().to_vec(),
+            dt: self.dt,
+            st: self.st,
+            sound: self.sound,
+            halted: self.kb_halt_reg,
+            error: self.error,
+            ram: self.ram.to_vec(),
+            screen: self.screen.extract_buffer().borrow().to_vec(),
+        }
+    }
+
+    //One 60th of a second without a frontend: up to ipf instructions, ending early at
+    //the first draw when waiting for vblank, then a timer tick. Returns the instructions run
+    pub fn run_frame(&mut self, ipf: u32, vblank: bool) -> u32 {
+        let executed = self.run_instructions(ipf, vblank);
+        self.update_timers();
+        executed
+    }
+
+    //The instructions of run_frame without its timer tick. Waiting on Fx0A ends them early,
+    //since only a key let go between frames can end the wait, and the error screen shown
+    //after a CPU error is not counted as the rom's
+    pub fn run_instructions(&mut self, ipf: u32, vblank: bool) -> u32 {
+        let mut executed = 0;
+        for _ in 0..ipf {
+            if self.halted() {
+                break;
+            }
+            if self.error.is_none() {
+                executed += 1;
+            }
+            if let Some(Chip8Event::RequestRedraw) = self.update()
+                && vblank
+            {
+                break;
+            }
+        }
+        executed
+    }
+
     pub fn update_timers(&mut self) {
+        self.kb.tick();
         if self.kb_halt_reg.is_none() {
             self.dt = self.dt.saturating_sub(1);
             self.st = self.st.saturating_sub(1);
+        } else {
+            //Any key will do while halted
+            self.kb.mark_all_polled();
         }
         if self.st == 0 {
-            self.buzzer.pause();
+            self.sound = false;
         }
     }
 
+    //Keys past F are ignored
     pub fn set_key(&mut self, key: u8, state: bool) {
+        if key > 0xF {
+            return;
+        }
         match state {
             true => self.press_key(key),
             false => self.release_key(key),
@@ -121,7 +243,7 @@ impl Chip8 {
             //00E0 CLS
             0x00E0 => self.screen.clear(),
             //00EE RET
-            0x00EE => self.pc = self.stack.pop(),
+            0x00EE => self.pc = self.stack.pop()?,
             //1nnn JP addr
             0x1000..=0x1FFF => {
                 self.pc = op & 0x0FFF;
@@ -129,7 +251,7 @@ impl Chip8 {
             }
             //2nnn CALL addr
             0x2000..=0x2FFF => {
-                self.stack.push(self.pc);
+                self.stack.push(self.pc)?;
                 self.pc = op & 0x0FFF;
                 return Ok(Chip8Event::DoNotIncrementPC);
             }
@@ -162,17 +284,23 @@ impl Chip8 {
                 //8xy1 OR Vx, Vy
                 0x01 => {
                     self.reg[x] |= vy;
-                    self.reg[0xF] = 0;
+                    if self.quirks.logic {
+                        self.reg[0xF] = 0;
+                    }
                 }
                 //8xy2 AND Vx, Vy
                 0x02 => {
                     self.reg[x] &= vy;
-                    self.reg[0xF] = 0;
+                    if self.quirks.logic {
+                        self.reg[0xF] = 0;
+                    }
                 }
                 //8xy3 XOR Vx, Vy
                 0x03 => {
                     self.reg[x] ^= vy;
-                    self.reg[0xF] = 0;
+                    if self.quirks.logic {
+                        self.reg[0xF] = 0;
+                    }
                 }
                 //8xy4 ADD Vx, Vy
                 0x04 => {
@@ -188,8 +316,9 @@ impl Chip8 {
                 }
                 //8xy6 SHR Vx {, Vy}
                 0x06 => {
-                    let out_bit = (vy & 0x01 == 1) as u8;
-                    self.reg[x] = vy >> 1;
+                    let v = if self.quirks.shift { vx } else { vy };
+                    let out_bit = (v & 0x01 == 1) as u8;
+                    self.reg[x] = v >> 1;
                     self.reg[0xF] = out_bit;
                 }
                 //8xy7 SUBN Vx, Vy
@@ -200,8 +329,9 @@ impl Chip8 {
                 }
                 //8xyE SHL Vx {, Vy}
                 0x0E => {
-                    let out_bit = (vy & 0x80 == 0x80) as u8;
-                    self.reg[x] = vy << 1;
+                    let v = if self.quirks.shift { vx } else { vy };
+                    let out_bit = (v & 0x80 == 0x80) as u8;
+                    self.reg[x] = v << 1;
                     self.reg[0xF] = out_bit;
                 }
                 _ => return Err(CPUError::UnknownOpcode(op, self.pc).into()),
@@ -216,19 +346,20 @@ impl Chip8 {
             0xA000..=0xAFFF => self.i = op & 0x0FFF,
             //Bnnn JP V0, addr
             0xB000..=0xBFFF => {
-                self.pc = (op & 0x0FFF) + self.reg[0x0] as u16;
+                let offset = if self.quirks.jump { vx } else { self.reg[0x0] };
+                self.pc = (op & 0x0FFF) + offset as u16;
                 return Ok(Chip8Event::DoNotIncrementPC);
             }
             //Cnnn RND Vx, byte
             0xC000..=0xCFFF => {
-                let r = rand::thread_rng().gen_range(0x00..=0xFF);
+                let r = self.rng.gen_range(0x00..=0xFF);
                 self.reg[x] = r & lb;
             }
             //Dxyn DRW Vx, Vy, n
             0xD000..=0xDFFF => {
                 let i = self.i as usize;
-                let sprite = &self.ram[i..(i + n as usize)];
-                self.reg[0xF] = self.screen.print_sprite(sprite, vx, vy) as u8;
+                let sprite = self.ram.get(i..i + n as usize).ok_or(CPUError::RamOutOfBounds)?;
+                self.reg[0xF] = self.screen.print_sprite(sprite, vx, vy, self.quirks.wrap) as u8;
                 return Ok(Chip8Event::RequestRedraw);
             }
             //E
@@ -237,6 +368,7 @@ impl Chip8 {
                 0x9E => {
                     #[cfg(feature = "kb_debug")]
                     println!("Checking for key press {:X}", x);
+                    self.kb.mark_polled((vx & 0xF) as usize);
                     if self.kb.is_pressed((vx & 0xF) as usize) {
                         return Ok(Chip8Event::SkipNextInstruction);
                     };
@@ -245,11 +377,12 @@ impl Chip8 {
                 0xA1 => {
                     #[cfg(feature = "kb_debug")]
                     println!("Checking key not pressed {:X}", x);
+                    self.kb.mark_polled((vx & 0xF) as usize);
                     if !self.kb.is_pressed((vx & 0xF) as usize) {
                         return Ok(Chip8Event::SkipNextInstruction);
                     };
                 }
-                _ => return Err(CPUError::RamOutOfBounds.into()),
+                _ => return Err(CPUError::UnknownOpcode(op, self.pc).into()),
             },
             //F
             0xF000..=0xFFFF => match lb {
@@ -268,7 +401,7 @@ impl Chip8 {
                 0x18 => {
                     #[cfg(feature = "sound_debug")]
                     println!("Received opcode to play sound");
-                    self.buzzer.play();
+                    self.sound = vx > 0;
                     self.st = vx;
                 }
                 //Fx1E ADD I, Vx
@@ -278,35 +411,29 @@ impl Chip8 {
                 //Fx33 LD B, Vx
                 0x33 => {
                     let i = self.i as usize;
-                    if let Ok([b0, b1, b2]) = self.ram.get_many_mut([i, i + 1, i + 2]) {
-                        *b0 = vx / 100;
-                        *b1 = vx / 10 % 10;
-                        *b2 = vx % 10;
+                    if let Some(digits) = self.ram.get_mut(i..i + 3) {
+                        digits.copy_from_slice(&[vx / 100, vx / 10 % 10, vx % 10]);
                     } else {
                         return Err(CPUError::RamOutOfBounds.into());
                     }
                 }
                 //Fx55 LD [I], Vx
                 0x55 => {
-                    (0..=x)
-                        .flat_map(|x| self.reg.get(x))
-                        .copied()
-                        .enumerate()
-                        .for_each(|(i, vx)| {
-                            self.ram[self.i as usize + i] = vx;
-                        });
-                    self.i += (x + 1) as u16;
+                    let i = self.i as usize;
+                    let ram = self.ram.get_mut(i..=i + x).ok_or(CPUError::RamOutOfBounds)?;
+                    ram.copy_from_slice(&self.reg[..=x]);
+                    if !self.quirks.memory_leave_i {
+                        self.i += (x + 1) as u16;
+                    }
                 }
                 //Fx65 LD Vx, [I]
                 0x65 => {
-                    (0..=x)
-                        .filter_map(|i| self.ram.get(self.i as usize + i))
-                        .copied()
-                        .enumerate()
-                        .for_each(|(i, vx)| {
-                            self.reg[i] = vx;
-                        });
-                    self.i += (x + 1) as u16;
+                    let i = self.i as usize;
+                    let ram = self.ram.get(i..=i + x).ok_or(CPUError::RamOutOfBounds)?;
+                    self.reg[..=x].copy_from_slice(ram);
+                    if !self.quirks.memory_leave_i {
+                        self.i += (x + 1) as u16;
+                    }
                 }
                 _ => return Err(CPUError::UnknownOpcode(op, self.pc).into()),
             },
@@ -321,7 +448,7 @@ impl Chip8 {
 
     pub fn update(&mut self) -> Option<Chip8Event> {
         if let Some(b1) = self.ram.get(self.pc as usize).map(|&e| e as u16)
-            && let Some(b2) = self.ram.get((self.pc + 1) as usize).map(|&e| e as u16)
+            && let Some(b2) = self.ram.get(self.pc as usize + 1).map(|&e| e as u16)
         {
             if self.kb_halt_reg.is_some() {
                 return None;
@@ -346,11 +473,13 @@ impl Chip8 {
                 },
                 Err(e) => {
                     eprintln!("{:?}", e);
+                    self.error = self.error.or(e.downcast_ref::<CPUError>().copied());
                     self.pc = ERR_LOC;
                 }
             }
         } else {
             eprintln!("{}", CPUError::RamOutOfBounds);
+            self.error = self.error.or(Some(CPUError::RamOutOfBounds));
             self.pc = ERR_LOC;
         }
         None
@@ -364,7 +493,7 @@ impl Chip8 {
         self.kb.press_key(key as usize);
 
         if self.kb_halt_reg.is_some() {
-            self.buzzer.play();
+            self.sound = true;
         }
     }
 
@@ -378,12 +507,12 @@ impl Chip8 {
         if let Some(x) = self.kb_halt_reg {
             self.reg[x] = self.kb.last_pressed();
             self.kb_halt_reg = None;
-            self.buzzer.pause();
+            self.sound = false;
         }
     }
 }
 
-const TEXT_SPRITES: [[u8; 5]; 16] = [
+pub const TEXT_SPRITES: [[u8; 5]; 16] = [
     [0xF0, 0x90, 0x90, 0x90, 0xF0], //0
     [0x20, 0x60, 0x20, 0x20, 0x70], //1
     [0xF0, 0x10, 0xF0, 0x80, 0xF0], //2
@@ -403,7 +532,7 @@ const TEXT_SPRITES: [[u8; 5]; 16] = [
 ];
 
 #[rustfmt::skip]
-const ERR_CODE: [u8; 12] = [
+pub(crate) const ERR_CODE: [u8; 12] = [
     //CLS
     0x00, 0xE0,
     //Print E
@@ -415,6 +544,12 @@ const ERR_CODE: [u8; 12] = [
     0x10, 0x5A, //hardcoded :)
 ];
 
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(debug_assertions)]
 impl fmt::Debug for Chip8 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -431,8 +566,8 @@ impl fmt::Debug for Chip8 {
                 })
                 .trim_end_matches('|'),
             self.dt,
-            self.ram[self.pc as usize],
-            self.ram[(self.pc + 1) as usize]
+            self.ram.get(self.pc as usize).copied().unwrap_or(0),
+            self.ram.get(self.pc as usize + 1).copied().unwrap_or(0)
         )
     }
 }
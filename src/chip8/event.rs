@@ -1,4 +1,4 @@
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Chip8Event {
     IncrementPC,
     KBHaltOnBuffer(usize),
@@ -1,10 +1,20 @@
 #[cfg(feature = "kb_trace")]
 use std::fmt::Write;
 
+//Frames a key stays marked as polled after the rom last checked it
+const POLL_FRAMES: u8 = 8;
+
 #[derive(Clone, Debug, Default)]
 pub struct Keyboard {
     pressed_keys: [bool; 16],
     last_pressed: u8,
+    polled: [u8; 16],
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeypadState {
+    pub held: [bool; 16],
+    pub polled: [bool; 16],
 }
 
 impl Keyboard {
@@ -44,4 +54,27 @@ impl Keyboard {
     pub fn last_pressed(&self) -> u8 {
         self.last_pressed
     }
+
+    pub fn mark_polled(&mut self, key: usize) {
+        if let Some(frames) = self.polled.get_mut(key) {
+            *frames = POLL_FRAMES;
+        }
+    }
+
+    pub fn mark_all_polled(&mut self) {
+        self.polled = [POLL_FRAMES; 16];
+    }
+
+    pub fn tick(&mut self) {
+        self.polled
+            .iter_mut()
+            .for_each(|frames| *frames = frames.saturating_sub(1));
+    }
+
+    pub fn state(&self) -> KeypadState {
+        KeypadState {
+            held: self.pressed_keys,
+            polled: self.polled.map(|frames| frames > 0),
+        }
+    }
 }
@@ -0,0 +1,124 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Error};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::default(),
+            Platform::SuperChip => Quirks {
+                logic: false,
+                shift: true,
+                memory_leave_i: true,
+                jump: true,
+                wrap: false,
+                vblank: false,
+            },
+            Platform::XoChip => Quirks {
+                logic: false,
+                shift: false,
+                memory_leave_i: false,
+                jump: false,
+                wrap: true,
+                vblank: false,
+            },
+        }
+    }
+
+    //Instructions per frame, matching the chip-8-database defaults
+    pub fn tick_rate(self) -> u32 {
+        match self {
+            Platform::Chip8 => 15,
+            Platform::SuperChip => 30,
+            Platform::XoChip => 100,
+        }
+    }
+
+    //Maps the platform ids used by the community chip-8-database
+    pub fn from_database_id(id: &str) -> Option<Platform> {
+        match id {
+            "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" => Some(Platform::Chip8),
+            "chip48" | "superchip1" | "superchip" | "megachip8" => Some(Platform::SuperChip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" | "vip" => Ok(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Platform::from_database_id(s)
+                .ok_or_else(|| anyhow!("Unknown platform \"{}\", expected chip8, schip or xochip", s)),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "CHIP-8"),
+            Platform::SuperChip => write!(f, "SUPER-CHIP"),
+            Platform::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+//Field names follow the quirk names of the community chip-8-database
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    //8xy1, 8xy2 and 8xy3 reset VF to 0
+    pub logic: bool,
+    //8xy6 and 8xyE shift Vx in place instead of Vy
+    pub shift: bool,
+    //Fx55 and Fx65 leave I unchanged
+    pub memory_leave_i: bool,
+    //Bnnn jumps to xnn + Vx instead of nnn + V0
+    pub jump: bool,
+    //Sprites wrap around the screen edges instead of being clipped
+    pub wrap: bool,
+    //Dxyn waits for the next frame before execution continues
+    pub vblank: bool,
+}
+
+impl Quirks {
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), Error> {
+        match name {
+            "logic" => self.logic = value,
+            "shift" => self.shift = value,
+            "memoryLeaveIUnchanged" | "memory_leave_i" => self.memory_leave_i = value,
+            "jump" => self.jump = value,
+            "wrap" => self.wrap = value,
+            "vblank" => self.vblank = value,
+            _ => return Err(anyhow!("Unknown quirk \"{}\"", name)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for Quirks {
+    //Original COSMAC VIP behaviour
+    fn default() -> Self {
+        Self {
+            logic: true,
+            shift: false,
+            memory_leave_i: false,
+            jump: false,
+            wrap: false,
+            vblank: true,
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::{cell::RefCell, rc::Rc};
 use crate::{HEIGHT, WIDTH};
 
 const BUFFER_LEN: usize = (WIDTH / 8) * HEIGHT;
-pub(crate) type ScreenBuffer = Rc<RefCell<[u8]>>;
+pub type ScreenBuffer = Rc<RefCell<[u8]>>;
 
 #[repr(transparent)]
 pub struct Screen(ScreenBuffer);
@@ -17,7 +17,7 @@ impl Default for Screen {
 impl Screen {
     const CLEAR: [u8; BUFFER_LEN] = [0x00_u8; BUFFER_LEN];
 
-    pub fn print_sprite(&mut self, sprite: &[u8], x: u8, y: u8) -> bool {
+    pub fn print_sprite(&mut self, sprite: &[u8], x: u8, y: u8, wrap: bool) -> bool {
         let (w, h) = (WIDTH as u8, HEIGHT as u8);
         let (x, y) = (x % w, y % h);
         let mut intersection = false;
@@ -27,7 +27,7 @@ impl Screen {
             let word_offset = (x % 8) as u32;
 
             let y = y.wrapping_add(i); //Wrapping due to cpu wrapping sub
-                                       // let y = y % h; //Wrap screen horizontally
+            let y = if wrap { y % h } else { y };
             if let Some(i) = Self::fix_index(x, y)
                 && let Some(lb) = self.0.borrow_mut().get_mut(i)
             {
@@ -41,7 +41,7 @@ impl Screen {
             }
             //Inserts to the next word (wrapping) if sprite crosses word boundary
             let x = x.wrapping_add(8); //Wrapping due to cpu wrapping sub
-                                       // let x = x % w; //Wrap screen horizontally
+            let x = if wrap { x % w } else { x };
             if x < 64
                 && let Some(i) = Self::fix_index(x, y)
                 && let Some(ub) = self.0.borrow_mut().get_mut(i)
@@ -75,8 +75,4 @@ impl Screen {
     pub fn clear(&mut self) {
         self.0 = Rc::new(RefCell::new(Self::CLEAR));
     }
-
-    pub fn default_buffer() -> ScreenBuffer {
-        Rc::new(RefCell::new(Self::CLEAR))
-    }
 }
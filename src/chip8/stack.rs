@@ -1,3 +1,5 @@
+use super::CPUError;
+
 #[derive(Clone, Default)]
 pub struct Stack {
     contents: [u16; 16],
@@ -5,17 +7,20 @@ pub struct Stack {
 }
 
 impl Stack {
-    pub fn push(&mut self, data: u16) {
-        if self.sp == 16 {
-            panic!("Chip-8 internal stack overflow");
-        }
-        *self.contents.get_mut(self.sp).unwrap() = data;
+    pub fn push(&mut self, data: u16) -> Result<(), CPUError> {
+        let slot = self.contents.get_mut(self.sp).ok_or(CPUError::StackOverflow)?;
+        *slot = data;
         self.sp += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u16, CPUError> {
+        self.sp = self.sp.checked_sub(1).ok_or(CPUError::StackUnderflow)?;
+        Ok(self.contents[self.sp])
     }
 
-    pub fn pop(&mut self) -> u16 {
-        self.sp = self.sp.saturating_sub(1);
-        self.contents[self.sp]
+    pub fn entries(&self) -> &[u16] {
+        &self.contents[..self.sp]
     }
 }
 
@@ -0,0 +1,602 @@
+use super::{event::Chip8Event, quirks::Quirks, screen::Screen, CPUError, Chip8, ENTRY_POINT};
+use crate::{HEIGHT, WIDTH};
+
+//Chip8::new only sets up memory, so tests can make as many as they like
+fn cpu() -> Chip8 {
+    Chip8::new()
+}
+
+fn cpu_with(quirks: Quirks) -> Chip8 {
+    Chip8::new().with_quirks(quirks)
+}
+
+fn exec(cpu: &mut Chip8, op: u16) -> Chip8Event {
+    cpu.execute_instruction(op)
+        .unwrap_or_else(|e| panic!("{:04X} failed: {}", op, e))
+}
+
+fn exec_err(cpu: &mut Chip8, op: u16) -> CPUError {
+    let e = cpu.execute_instruction(op).expect_err("expected an error");
+    *e.downcast_ref::<CPUError>().expect("expected a CPUError")
+}
+
+fn lit(screen: &Screen, x: usize, y: usize) -> bool {
+    let buffer = screen.extract_buffer();
+    let i = y * WIDTH + x;
+    let lit = buffer.borrow()[i / 8] & (0x80 >> (i % 8)) != 0;
+    lit
+}
+
+fn lit_count(screen: &Screen) -> u32 {
+    let buffer = screen.extract_buffer();
+    let count = buffer.borrow().iter().map(|b| b.count_ones()).sum();
+    count
+}
+
+//Arithmetic, logic and shifts, returning Vx and VF
+fn alu(quirks: Quirks, op: u16, vx: u8, vy: u8) -> (u8, u8) {
+    let mut cpu = cpu_with(quirks);
+    let (x, y) = (((op >> 8) & 0xF) as usize, ((op >> 4) & 0xF) as usize);
+    cpu.reg[0xF] = 0xAA;
+    cpu.reg[x] = vx;
+    cpu.reg[y] = vy;
+    assert_eq!(exec(&mut cpu, op), Chip8Event::IncrementPC);
+    (cpu.reg[x], cpu.reg[0xF])
+}
+
+#[test]
+fn clear_screen() {
+    let mut cpu = cpu();
+    cpu.screen.print_sprite(&[0xFF], 0, 0, false);
+    assert_eq!(exec(&mut cpu, 0x00E0), Chip8Event::IncrementPC);
+    assert_eq!(lit_count(&cpu.screen), 0);
+}
+
+#[test]
+fn call_and_return() {
+    let mut cpu = cpu();
+    assert_eq!(exec(&mut cpu, 0x2345), Chip8Event::DoNotIncrementPC);
+    assert_eq!(cpu.pc, 0x345);
+    //Returns to the call itself, which the pc increment then steps over
+    assert_eq!(exec(&mut cpu, 0x00EE), Chip8Event::IncrementPC);
+    assert_eq!(cpu.pc, ENTRY_POINT);
+}
+
+#[test]
+fn nested_calls_return_in_order() {
+    let mut cpu = cpu();
+    exec(&mut cpu, 0x2300);
+    exec(&mut cpu, 0x2400);
+    exec(&mut cpu, 0x00EE);
+    assert_eq!(cpu.pc, 0x300);
+    exec(&mut cpu, 0x00EE);
+    assert_eq!(cpu.pc, ENTRY_POINT);
+}
+
+#[test]
+fn jump() {
+    let mut cpu = cpu();
+    assert_eq!(exec(&mut cpu, 0x1ABC), Chip8Event::DoNotIncrementPC);
+    assert_eq!(cpu.pc, 0xABC);
+}
+
+#[test]
+fn blank_memory_does_nothing() {
+    let mut cpu = cpu();
+    assert_eq!(exec(&mut cpu, 0x0000), Chip8Event::DoNotIncrementPC);
+    assert_eq!(cpu.pc, ENTRY_POINT);
+}
+
+#[test]
+fn skip_if_equal_byte() {
+    let mut cpu = cpu();
+    cpu.reg[3] = 0x42;
+    assert_eq!(exec(&mut cpu, 0x3342), Chip8Event::SkipNextInstruction);
+    assert_eq!(exec(&mut cpu, 0x3343), Chip8Event::IncrementPC);
+}
+
+#[test]
+fn skip_if_not_equal_byte() {
+    let mut cpu = cpu();
+    cpu.reg[3] = 0x42;
+    assert_eq!(exec(&mut cpu, 0x4343), Chip8Event::SkipNextInstruction);
+    assert_eq!(exec(&mut cpu, 0x4342), Chip8Event::IncrementPC);
+}
+
+#[test]
+fn skip_if_registers_equal() {
+    let mut cpu = cpu();
+    cpu.reg[1] = 7;
+    cpu.reg[2] = 7;
+    assert_eq!(exec(&mut cpu, 0x5120), Chip8Event::SkipNextInstruction);
+    cpu.reg[2] = 8;
+    assert_eq!(exec(&mut cpu, 0x5120), Chip8Event::IncrementPC);
+}
+
+#[test]
+fn skip_if_registers_differ() {
+    let mut cpu = cpu();
+    cpu.reg[1] = 7;
+    cpu.reg[2] = 8;
+    assert_eq!(exec(&mut cpu, 0x9120), Chip8Event::SkipNextInstruction);
+    cpu.reg[2] = 7;
+    assert_eq!(exec(&mut cpu, 0x9120), Chip8Event::IncrementPC);
+}
+
+#[test]
+fn register_skips_need_a_zero_nibble() {
+    let mut cpu = cpu();
+    assert!(matches!(exec_err(&mut cpu, 0x5121), CPUError::UnknownOpcode(0x5121, _)));
+    assert!(matches!(exec_err(&mut cpu, 0x912F), CPUError::UnknownOpcode(0x912F, _)));
+}
+
+#[test]
+fn skipping_steps_over_the_next_instruction() {
+    let mut cpu = cpu().with_rom(&[0x30, 0x00, 0x60, 0x01, 0x61, 0x01]);
+    cpu.update();
+    cpu.update();
+    assert_eq!(cpu.reg[0], 0);
+    assert_eq!(cpu.reg[1], 1);
+    assert_eq!(cpu.pc, ENTRY_POINT + 6);
+}
+
+#[test]
+fn load_and_add_byte() {
+    let mut cpu = cpu();
+    assert_eq!(exec(&mut cpu, 0x6A12), Chip8Event::IncrementPC);
+    assert_eq!(cpu.reg[0xA], 0x12);
+    cpu.reg[0xF] = 0x55;
+    assert_eq!(exec(&mut cpu, 0x7AF0), Chip8Event::IncrementPC);
+    //Wraps around and never touches VF
+    assert_eq!(cpu.reg[0xA], 0x02);
+    assert_eq!(cpu.reg[0xF], 0x55);
+}
+
+#[test]
+fn load_register() {
+    assert_eq!(alu(Quirks::default(), 0x8120, 0x11, 0x22), (0x22, 0xAA));
+}
+
+#[test]
+fn logic_resets_vf_with_the_quirk() {
+    let vip = Quirks::default();
+    assert_eq!(alu(vip, 0x8121, 0x0F, 0xF0), (0xFF, 0));
+    assert_eq!(alu(vip, 0x8122, 0x0C, 0x0A), (0x08, 0));
+    assert_eq!(alu(vip, 0x8123, 0x0C, 0x0A), (0x06, 0));
+    let modern = Quirks { logic: false, ..vip };
+    assert_eq!(alu(modern, 0x8121, 0x0F, 0xF0), (0xFF, 0xAA));
+    assert_eq!(alu(modern, 0x8122, 0x0C, 0x0A), (0x08, 0xAA));
+    assert_eq!(alu(modern, 0x8123, 0x0C, 0x0A), (0x06, 0xAA));
+}
+
+#[test]
+fn add_sets_carry() {
+    let quirks = Quirks::default();
+    assert_eq!(alu(quirks, 0x8124, 0x10, 0x20), (0x30, 0));
+    assert_eq!(alu(quirks, 0x8124, 0xFF, 0x01), (0x00, 1));
+    assert_eq!(alu(quirks, 0x8124, 0xF0, 0x20), (0x10, 1));
+}
+
+#[test]
+fn subtract_clears_vf_on_borrow() {
+    let quirks = Quirks::default();
+    assert_eq!(alu(quirks, 0x8125, 0x30, 0x10), (0x20, 1));
+    assert_eq!(alu(quirks, 0x8125, 0x10, 0x30), (0xE0, 0));
+    //Equal values do not borrow
+    assert_eq!(alu(quirks, 0x8125, 0x10, 0x10), (0x00, 1));
+}
+
+#[test]
+fn reverse_subtract_clears_vf_on_borrow() {
+    let quirks = Quirks::default();
+    assert_eq!(alu(quirks, 0x8127, 0x10, 0x30), (0x20, 1));
+    assert_eq!(alu(quirks, 0x8127, 0x30, 0x10), (0xE0, 0));
+    assert_eq!(alu(quirks, 0x8127, 0x10, 0x10), (0x00, 1));
+}
+
+#[test]
+fn shifts_use_vy_without_the_quirk() {
+    let vip = Quirks::default();
+    assert_eq!(alu(vip, 0x8126, 0xFF, 0x05), (0x02, 1));
+    assert_eq!(alu(vip, 0x8126, 0xFF, 0x04), (0x02, 0));
+    assert_eq!(alu(vip, 0x812E, 0x00, 0x81), (0x02, 1));
+    assert_eq!(alu(vip, 0x812E, 0x00, 0x41), (0x82, 0));
+}
+
+#[test]
+fn shifts_use_vx_with_the_quirk() {
+    let schip = Quirks { shift: true, ..Quirks::default() };
+    assert_eq!(alu(schip, 0x8126, 0x05, 0xFF), (0x02, 1));
+    assert_eq!(alu(schip, 0x8126, 0x04, 0xFF), (0x02, 0));
+    assert_eq!(alu(schip, 0x812E, 0x81, 0x00), (0x02, 1));
+    assert_eq!(alu(schip, 0x812E, 0x41, 0x00), (0x82, 0));
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_target() {
+    let quirks = Quirks::default();
+    let mut cpu = cpu_with(quirks);
+    cpu.reg[0xF] = 0x10;
+    cpu.reg[1] = 0x01;
+    exec(&mut cpu, 0x8F14);
+    assert_eq!(cpu.reg[0xF], 0);
+    cpu.reg[0xF] = 0x03;
+    exec(&mut cpu, 0x8F15);
+    assert_eq!(cpu.reg[0xF], 1);
+    //The shifted out bit, not the result of 0
+    cpu.reg[0xF] = 0x03;
+    exec(&mut cpu, 0x8F16);
+    assert_eq!(cpu.reg[0xF], 1);
+}
+
+#[test]
+fn vf_can_be_the_source() {
+    let mut cpu = cpu();
+    cpu.reg[0xF] = 0xFF;
+    cpu.reg[1] = 0x01;
+    exec(&mut cpu, 0x81F4);
+    assert_eq!((cpu.reg[1], cpu.reg[0xF]), (0x00, 1));
+}
+
+#[test]
+fn unknown_alu_opcodes_fail() {
+    let mut cpu = cpu();
+    for n in [0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xF] {
+        let op = 0x8120 | n;
+        assert!(matches!(exec_err(&mut cpu, op), CPUError::UnknownOpcode(o, _) if o == op));
+    }
+}
+
+#[test]
+fn load_index() {
+    let mut cpu = cpu();
+    assert_eq!(exec(&mut cpu, 0xA123), Chip8Event::IncrementPC);
+    assert_eq!(cpu.i, 0x123);
+}
+
+#[test]
+fn jump_with_offset() {
+    let mut cpu = cpu();
+    cpu.reg[0] = 0x10;
+    cpu.reg[3] = 0x20;
+    assert_eq!(exec(&mut cpu, 0xB300), Chip8Event::DoNotIncrementPC);
+    assert_eq!(cpu.pc, 0x310);
+
+    let mut cpu = cpu_with(Quirks { jump: true, ..Quirks::default() });
+    cpu.reg[0] = 0x10;
+    cpu.reg[3] = 0x20;
+    exec(&mut cpu, 0xB300);
+    assert_eq!(cpu.pc, 0x320);
+}
+
+#[test]
+fn random_is_masked() {
+    let mut cpu = cpu();
+    cpu.reg[4] = 0xFF;
+    exec(&mut cpu, 0xC400);
+    assert_eq!(cpu.reg[4], 0);
+    for _ in 0..64 {
+        assert_eq!(exec(&mut cpu, 0xC40F), Chip8Event::IncrementPC);
+        assert!(cpu.reg[4] <= 0x0F);
+    }
+}
+
+#[test]
+fn draw_requests_a_redraw() {
+    let mut cpu = cpu();
+    cpu.i = 0; //The font's 0
+    cpu.reg[1] = 0;
+    cpu.reg[2] = 0;
+    assert_eq!(exec(&mut cpu, 0xD125), Chip8Event::RequestRedraw);
+    assert_eq!(cpu.reg[0xF], 0);
+    assert_eq!(lit_count(&cpu.screen), 14);
+    //Drawing it again erases it and reports the collision
+    assert_eq!(exec(&mut cpu, 0xD125), Chip8Event::RequestRedraw);
+    assert_eq!(cpu.reg[0xF], 1);
+    assert_eq!(lit_count(&cpu.screen), 0);
+}
+
+#[test]
+fn draw_starts_wrapped() {
+    let mut cpu = cpu();
+    cpu.ram[0x300] = 0x80;
+    cpu.i = 0x300;
+    cpu.reg[1] = 70;
+    cpu.reg[2] = 33;
+    exec(&mut cpu, 0xD121);
+    assert!(lit(&cpu.screen, 6, 1));
+    assert_eq!(lit_count(&cpu.screen), 1);
+}
+
+#[test]
+fn draw_clips_at_the_edges_without_the_quirk() {
+    let mut cpu = cpu();
+    cpu.ram[0x300..0x304].copy_from_slice(&[0xFF; 4]);
+    cpu.i = 0x300;
+    cpu.reg[1] = 60;
+    cpu.reg[2] = 30;
+    exec(&mut cpu, 0xD124);
+    assert_eq!(lit_count(&cpu.screen), 8);
+    assert!(lit(&cpu.screen, 63, 31));
+    assert!(!lit(&cpu.screen, 0, 30));
+    assert!(!lit(&cpu.screen, 60, 0));
+}
+
+#[test]
+fn draw_wraps_at_the_edges_with_the_quirk() {
+    let mut cpu = cpu_with(Quirks { wrap: true, ..Quirks::default() });
+    cpu.ram[0x300..0x304].copy_from_slice(&[0xFF; 4]);
+    cpu.i = 0x300;
+    cpu.reg[1] = 60;
+    cpu.reg[2] = 30;
+    exec(&mut cpu, 0xD124);
+    assert_eq!(lit_count(&cpu.screen), 32);
+    assert!(lit(&cpu.screen, 63, 31));
+    assert!(lit(&cpu.screen, 3, 1));
+    assert!(lit(&cpu.screen, 60, 0));
+    assert!(!lit(&cpu.screen, 4, 0));
+}
+
+#[test]
+fn collisions_at_the_edges() {
+    //A sprite straddling the right edge hits what is on screen, not what was clipped
+    let mut screen = Screen::default();
+    screen.print_sprite(&[0x10], 56, 5, false);
+    assert!(!screen.print_sprite(&[0xFF], 60, 5, false));
+    assert!(screen.print_sprite(&[0x80], 63, 5, false));
+    screen.print_sprite(&[0x80], 0, 6, false);
+    assert!(!screen.print_sprite(&[0xFF], 60, 6, false));
+
+    //With wrapping the part that comes back in on the left can collide too
+    let mut screen = Screen::default();
+    screen.print_sprite(&[0x80], 2, 10, true);
+    assert!(screen.print_sprite(&[0xFF], 59, 10, true));
+    assert!(!lit(&screen, 2, 10));
+
+    //Or at the top, coming in from the bottom
+    let mut screen = Screen::default();
+    screen.print_sprite(&[0x80], 0, 0, true);
+    assert!(screen.print_sprite(&[0x00, 0x80], 0, HEIGHT as u8 - 1, true));
+    assert!(!screen.print_sprite(&[0x00, 0x80], 0, HEIGHT as u8 - 1, false));
+}
+
+#[test]
+fn skip_if_key() {
+    let mut cpu = cpu();
+    cpu.reg[2] = 0x5;
+    assert_eq!(exec(&mut cpu, 0xE29E), Chip8Event::IncrementPC);
+    assert_eq!(exec(&mut cpu, 0xE2A1), Chip8Event::SkipNextInstruction);
+    cpu.set_key(0x5, true);
+    assert_eq!(exec(&mut cpu, 0xE29E), Chip8Event::SkipNextInstruction);
+    assert_eq!(exec(&mut cpu, 0xE2A1), Chip8Event::IncrementPC);
+    //Only the low nibble picks the key, and checking it marks it as polled
+    cpu.reg[2] = 0xF5;
+    assert_eq!(exec(&mut cpu, 0xE29E), Chip8Event::SkipNextInstruction);
+    assert!(cpu.keypad_state().polled[0x5]);
+    assert!(!cpu.keypad_state().polled[0x6]);
+}
+
+#[test]
+fn unknown_key_opcodes_fail() {
+    let mut cpu = cpu();
+    assert!(matches!(exec_err(&mut cpu, 0xE200), CPUError::UnknownOpcode(0xE200, _)));
+}
+
+#[test]
+fn timers() {
+    let mut cpu = cpu();
+    cpu.reg[3] = 0x20;
+    assert_eq!(exec(&mut cpu, 0xF315), Chip8Event::IncrementPC);
+    cpu.update_timers();
+    assert_eq!(exec(&mut cpu, 0xF407), Chip8Event::IncrementPC);
+    assert_eq!(cpu.reg[4], 0x1F);
+
+    assert_eq!(exec(&mut cpu, 0xF318), Chip8Event::IncrementPC);
+    assert_eq!(cpu.st, 0x20);
+    assert!(cpu.sound_active());
+    cpu.reg[3] = 0;
+    exec(&mut cpu, 0xF318);
+    assert!(!cpu.sound_active());
+}
+
+#[test]
+fn wait_for_key() {
+    let mut cpu = cpu();
+    assert_eq!(exec(&mut cpu, 0xF70A), Chip8Event::KBHaltOnBuffer(7));
+    //The VIP beeps briefly while waiting
+    assert_eq!(cpu.st, 4);
+}
+
+#[test]
+fn wait_for_key_takes_the_released_key() {
+    let mut cpu = cpu().with_rom(&[0xF7, 0x0A, 0x61, 0x01]);
+    cpu.update();
+    cpu.update();
+    assert_eq!(cpu.reg[1], 0, "halted until a key comes up");
+    cpu.set_key(0xB, true);
+    cpu.update();
+    assert_eq!(cpu.reg[7], 0);
+    cpu.set_key(0xB, false);
+    assert_eq!(cpu.reg[7], 0xB);
+    cpu.update();
+    assert_eq!(cpu.reg[1], 1);
+}
+
+#[test]
+fn frames_count_only_instructions_that_ran() {
+    let mut cpu = cpu().with_rom(&[0x61, 0x01, 0xF7, 0x0A, 0x12, 0x04]);
+    assert_eq!(cpu.run_frame(15, false), 2);
+    assert_eq!(cpu.run_frame(15, false), 0, "halted until a key comes up");
+    cpu.set_key(0xB, true);
+    cpu.set_key(0xB, false);
+    assert_eq!(cpu.run_frame(15, false), 15);
+
+    //The error screen keeps drawing, but none of it is the rom's
+    let mut failing = Chip8::new().with_rom(&[0x61, 0x01, 0xFF, 0xFF]);
+    assert_eq!(failing.run_frame(15, false), 2);
+    assert_eq!(failing.run_frame(15, false), 0);
+}
+
+#[test]
+fn add_to_index() {
+    let mut cpu = cpu();
+    cpu.i = 0x0FFF;
+    cpu.reg[2] = 2;
+    assert_eq!(exec(&mut cpu, 0xF21E), Chip8Event::IncrementPC);
+    assert_eq!(cpu.i, 0x1001);
+    assert_eq!(cpu.reg[0xF], 0, "no carry into VF");
+}
+
+#[test]
+fn font_character() {
+    let mut cpu = cpu();
+    cpu.reg[2] = 0xA;
+    assert_eq!(exec(&mut cpu, 0xF229), Chip8Event::IncrementPC);
+    assert_eq!(cpu.i, 0xA * 5);
+    assert_eq!(cpu.ram[cpu.i as usize..cpu.i as usize + 5], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+    //Only the low nibble counts
+    cpu.reg[2] = 0x1A;
+    exec(&mut cpu, 0xF229);
+    assert_eq!(cpu.i, 0xA * 5);
+}
+
+#[test]
+fn binary_coded_decimal() {
+    let mut cpu = cpu();
+    cpu.i = 0x300;
+    for (value, digits) in [(254, [2, 5, 4]), (7, [0, 0, 7]), (40, [0, 4, 0]), (100, [1, 0, 0])] {
+        cpu.reg[3] = value;
+        assert_eq!(exec(&mut cpu, 0xF333), Chip8Event::IncrementPC);
+        assert_eq!(cpu.ram[0x300..0x303], digits, "{}", value);
+    }
+    assert_eq!(cpu.i, 0x300, "I stays put");
+}
+
+#[test]
+fn binary_coded_decimal_past_the_end_of_ram() {
+    let mut cpu = cpu();
+    cpu.i = 0xFFE;
+    assert!(matches!(exec_err(&mut cpu, 0xF333), CPUError::RamOutOfBounds));
+}
+
+#[test]
+fn save_and_load_move_index_without_the_quirk() {
+    let mut cpu = cpu();
+    cpu.i = 0x300;
+    cpu.reg[..4].copy_from_slice(&[1, 2, 3, 4]);
+    assert_eq!(exec(&mut cpu, 0xF355), Chip8Event::IncrementPC);
+    assert_eq!(cpu.ram[0x300..0x305], [1, 2, 3, 4, 0]);
+    assert_eq!(cpu.i, 0x304);
+
+    cpu.i = 0x300;
+    cpu.reg = [0; 16];
+    assert_eq!(exec(&mut cpu, 0xF265), Chip8Event::IncrementPC);
+    assert_eq!(cpu.reg[..4], [1, 2, 3, 0]);
+    assert_eq!(cpu.i, 0x303);
+}
+
+#[test]
+fn save_and_load_leave_index_with_the_quirk() {
+    let mut cpu = cpu_with(Quirks { memory_leave_i: true, ..Quirks::default() });
+    cpu.i = 0x300;
+    cpu.reg[..2].copy_from_slice(&[9, 8]);
+    exec(&mut cpu, 0xF155);
+    assert_eq!(cpu.i, 0x300);
+    cpu.reg = [0; 16];
+    exec(&mut cpu, 0xF165);
+    assert_eq!(cpu.reg[..2], [9, 8]);
+    assert_eq!(cpu.i, 0x300);
+}
+
+#[test]
+fn save_and_load_all_registers() {
+    let mut cpu = cpu();
+    cpu.i = 0x300;
+    cpu.reg = core::array::from_fn(|i| i as u8 * 3);
+    exec(&mut cpu, 0xFF55);
+    let saved = cpu.reg;
+    cpu.i = 0x300;
+    cpu.reg = [0; 16];
+    exec(&mut cpu, 0xFF65);
+    assert_eq!(cpu.reg, saved);
+    assert_eq!(cpu.i, 0x310);
+}
+
+#[test]
+fn unknown_opcodes_fail() {
+    let mut cpu = cpu();
+    for op in [0xF2FF, 0xF200, 0x0123, 0xE1FF] {
+        assert!(matches!(exec_err(&mut cpu, op), CPUError::UnknownOpcode(o, pc) if o == op && pc == ENTRY_POINT));
+    }
+}
+
+#[test]
+fn errors_jump_to_the_error_screen() {
+    let mut cpu = cpu().with_rom(&[0xFF, 0xFF]);
+    cpu.update();
+    assert_eq!(cpu.pc, super::ERR_LOC);
+    assert!(matches!(cpu.error(), Some(CPUError::UnknownOpcode(0xFFFF, ENTRY_POINT))));
+}
+
+#[test]
+fn too_many_calls_overflow_the_stack() {
+    let mut cpu = cpu();
+    for _ in 0..16 {
+        exec(&mut cpu, 0x2300);
+    }
+    assert!(matches!(exec_err(&mut cpu, 0x2300), CPUError::StackOverflow));
+}
+
+#[test]
+fn returning_without_a_call_underflows_the_stack() {
+    let mut cpu = cpu();
+    assert!(matches!(exec_err(&mut cpu, 0x00EE), CPUError::StackUnderflow));
+}
+
+#[test]
+fn sprites_past_the_end_of_ram() {
+    let mut cpu = cpu();
+    cpu.i = 0xFFC;
+    exec(&mut cpu, 0xD124);
+    assert!(matches!(exec_err(&mut cpu, 0xD125), CPUError::RamOutOfBounds));
+    //I can be pushed far past RAM by Fx1E
+    cpu.i = 0xFFFF;
+    assert!(matches!(exec_err(&mut cpu, 0xD121), CPUError::RamOutOfBounds));
+}
+
+#[test]
+fn save_and_load_past_the_end_of_ram() {
+    let mut cpu = cpu();
+    cpu.i = 0xFFE;
+    exec(&mut cpu, 0xF155);
+    assert_eq!(cpu.i, 0x1000);
+    cpu.i = 0xFFE;
+    assert!(matches!(exec_err(&mut cpu, 0xF255), CPUError::RamOutOfBounds));
+    assert!(matches!(exec_err(&mut cpu, 0xF265), CPUError::RamOutOfBounds));
+    assert_eq!(cpu.i, 0xFFE, "I is left alone on a fault");
+    cpu.i = 0xFFFF;
+    assert!(matches!(exec_err(&mut cpu, 0xF065), CPUError::RamOutOfBounds));
+}
+
+#[test]
+fn running_off_the_end_of_ram() {
+    let mut cpu = cpu();
+    cpu.pc = 0xFFF;
+    cpu.update();
+    assert_eq!(cpu.pc, super::ERR_LOC);
+    assert!(matches!(cpu.error(), Some(CPUError::RamOutOfBounds)));
+}
+
+#[test]
+fn roms_too_big_for_ram_are_cut_short() {
+    let cpu = cpu().with_rom(&[0xAB; 0x1000]);
+    assert_eq!(cpu.ram[0xFFF], 0xAB);
+}
+
+#[test]
+fn keys_past_f_are_ignored() {
+    let mut cpu = cpu();
+    cpu.set_key(0x10, true);
+    cpu.set_key(0xFF, false);
+    assert!(!cpu.keypad_state().held.contains(&true));
+}
@@ -1,70 +1,213 @@
 use std::{
-    env, fs,
+    fs,
     io::{BufReader, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
 };
+use anyhow::Error;
 use winit::event_loop::EventLoopProxy;
 
-use crate::chip8::{event::Chip8Event, screen::ScreenBuffer, Chip8, ENTRY_POINT};
+use crate::{
+    buzzer::{Buzzer, Edge, Sound, SAMPLES_PER_TICK},
+    chip8::{
+        event::Chip8Event, keyboard::KeypadState, quirks::Quirks, screen::ScreenBuffer, Chip8,
+        ENTRY_POINT,
+    },
+    palette::Palette,
+    screenshot,
+};
 
-const CPU_IPF: u32 = 15;
-const FF_IPF: u32 = CPU_IPF * 16;
+const FF_MULTIPLIER: u32 = 16;
 const MAX_FILESIZE: u64 = 0x1000 - ENTRY_POINT as u64;
 
 pub struct Chip8Handler {
     ipf: u32,
+    tick_rate: u32,
+    quirks: Quirks,
+    sound: Sound,
+    buzzer: Buzzer,
+    //Timer ticks run so far, which is what emulated time is counted in
+    ticks: u64,
+    sound_on: bool,
+    //Buzzer changes since the start of the last frame, for recordings to replay
+    edges: Vec<Edge>,
+    frame_start: u64,
+    rom_path: PathBuf,
     cpu: Chip8,
     sys_tx: EventLoopProxy<Chip8Event>,
     ff: bool,
+    paused: bool,
+    //A single frame to run while paused
+    step: bool,
 }
 
 impl Chip8Handler {
-    pub fn new(sys_tx: EventLoopProxy<Chip8Event>) -> Chip8Handler {
+    pub fn new(
+        sys_tx: EventLoopProxy<Chip8Event>,
+        rom_path: PathBuf,
+        quirks: Quirks,
+        tick_rate: u32,
+        sound: Sound,
+    ) -> Chip8Handler {
         Chip8Handler {
-            ipf: CPU_IPF,
-            cpu: Chip8::new().with_rom(&Self::read_rom_from_fs()),
+            ipf: tick_rate,
+            tick_rate,
+            quirks,
+            sound,
+            buzzer: Buzzer::new(sound),
+            ticks: 0,
+            sound_on: false,
+            edges: Vec::new(),
+            frame_start: 0,
+            cpu: Self::boot(&rom_path, quirks),
+            rom_path,
             sys_tx,
             ff: false,
+            paused: false,
+            step: false,
         }
     }
 
-    pub fn update(&mut self) {
-        for _ in 0..self.ipf {
-            if let Some(Chip8Event::RequestRedraw) = self.cpu.update()
-                //Ignore cpu-requested redraw events while in fast-forward
-                    && !self.ff
-            {
-                self.sys_tx.send_event(Chip8Event::RequestRedraw).unwrap();
-                break;
-            }
+    fn boot(rom_path: &Path, quirks: Quirks) -> Chip8 {
+        Chip8::new()
+            .with_quirks(quirks)
+            .with_rom(&Self::read_rom_from_fs(rom_path))
+    }
+
+    //Emulated time in audio samples
+    fn now(&self) -> u64 {
+        self.ticks * SAMPLES_PER_TICK
+    }
+
+    //Queues an edge whenever the Chip-8 turns the buzzer on or off
+    fn sync_sound(&mut self) {
+        let on = self.cpu.sound_active();
+        if on != self.sound_on {
+            self.sound_on = on;
+            let edge = Edge { at: self.now(), on };
+            self.edges.push(edge);
+            self.buzzer.push(edge);
         }
+    }
+
+    //Returns how many instructions ran, fewer than ipf when the frame waits for vblank or a
+    //key, or nothing when paused and no frame was run
+    pub fn update(&mut self) -> Option<u32> {
+        if self.paused && !std::mem::take(&mut self.step) {
+            return None;
+        }
+        let now = self.now();
+        self.edges.retain(|edge| edge.at >= now);
+        self.frame_start = now;
+        //Fast-forward ignores the wait for vblank
+        let executed = self.cpu.run_instructions(self.ipf, self.quirks.vblank && !self.ff);
+        self.sync_sound();
         self.cpu.update_timers();
+        self.ticks += 1;
+        self.sync_sound();
+        self.buzzer.advance(self.now());
+        Some(executed)
+    }
+
+    //The buzzer is muted while paused, the sound timer is left alone
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.step = false;
+        self.buzzer.set_muted(self.paused || self.sound.muted);
+    }
+
+    pub fn sound(&self) -> Sound {
+        self.sound
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.sound.volume = volume.clamp(0.0, 1.0);
+        self.buzzer.set_volume(self.sound.volume);
+    }
+
+    pub fn set_sound_muted(&mut self, muted: bool) {
+        self.sound.muted = muted;
+        self.buzzer.set_muted(self.paused || self.sound.muted);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    //Paused with no frame step waiting, so updating would run nothing
+    pub fn idle(&self) -> bool {
+        self.paused && !self.step
+    }
+
+    //Pauses if needed and runs exactly one frame of instructions and timer tick on the next update
+    pub fn advance_frame(&mut self) {
+        if !self.paused {
+            self.set_paused(true);
+        }
+        self.step = true;
     }
 
     pub fn update_key(&mut self, key: u8, state: bool) {
-        self.cpu.set_key(key, state)
+        self.cpu.set_key(key, state);
+        self.sync_sound();
     }
 
     pub fn start_ff(&mut self) {
         self.ff = true;
-        self.ipf = FF_IPF;
+        self.ipf = self.tick_rate * FF_MULTIPLIER;
     }
 
     pub fn stop_ff(&mut self) {
         self.ff = false;
-        self.ipf = CPU_IPF;
+        self.ipf = self.tick_rate;
+    }
+
+    //Multiple of the normal instructions per frame currently being run
+    pub fn speed(&self) -> u32 {
+        self.ipf / self.tick_rate.max(1)
+    }
+
+    pub fn keypad_state(&self) -> KeypadState {
+        self.cpu.keypad_state()
+    }
+
+    //Start of the last frame in emulated time and the buzzer edges since then
+    pub fn frame_sound(&self) -> (u64, &[Edge]) {
+        (self.frame_start, &self.edges)
+    }
+
+    //Whether the buzzer is sounding at the end of the last frame
+    pub fn sound_playing(&self) -> bool {
+        self.sound_on
     }
 
     pub fn get_frame_buffer(&self) -> ScreenBuffer {
         self.cpu.get_display_buffer()
     }
 
+    //Saves the raw 64x32 screen and a palette-colored copy scaled up, returning both paths
+    pub fn save_screenshot(&self, dir: &Path, palette: &Palette, scale: u32) -> Result<[PathBuf; 2], Error> {
+        let buffer = self.get_frame_buffer();
+        let buffer = buffer.borrow();
+        let timestamp = screenshot::timestamp(SystemTime::now());
+        let raw = screenshot::file_path(dir, &self.rom_path, &timestamp, ".png");
+        let scaled = screenshot::file_path(dir, &self.rom_path, &timestamp, &format!("-x{}.png", scale));
+        screenshot::save_raw(&buffer, &raw)?;
+        screenshot::save_scaled(&buffer, &scaled, palette, scale)?;
+        Ok([raw, scaled])
+    }
+
+    pub fn rom_path(&self) -> &Path {
+        &self.rom_path
+    }
+
     pub fn reset(&mut self) {
-        self.cpu = Chip8::new().with_rom(&Self::read_rom_from_fs());
+        self.cpu = Self::boot(&self.rom_path, self.quirks);
+        self.sync_sound();
         self.sys_tx.send_event(Chip8Event::RequestRedraw).unwrap();
     }
 
-    fn read_rom_from_fs() -> Vec<u8> {
-        let rom_path = env::args().nth(1).expect("Please provide a path to rom");
+    pub fn read_rom_from_fs(rom_path: &Path) -> Vec<u8> {
         let rom = fs::File::open(rom_path).expect("Cannot open rom, does it exist in the path?");
         let rom_metadata = rom.metadata().expect("Cannot access file metadata");
         if rom_metadata.len() > MAX_FILESIZE {
@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use gilrs::Button;
+use serde::Deserialize;
+use winit::keyboard::KeyCode;
+
+use crate::{
+    bindings::Hotkey,
+    buzzer::Waveform,
+    layout::{KeypadPosition, Rotation, ScaleMode},
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "chip8.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    //Chip-8 keys 0-F to the physical keys that press them
+    pub keys: BTreeMap<String, Vec<KeyCode>>,
+    pub hotkeys: BTreeMap<Hotkey, Vec<KeyCode>>,
+    pub gamepad: GamepadConfig,
+    pub keypad: KeypadConfig,
+    pub display: DisplayConfig,
+    pub palette: PaletteConfig,
+    pub effects: EffectsConfig,
+    pub shaders: ShadersConfig,
+    pub screenshots: ScreenshotsConfig,
+    pub recording: RecordingConfig,
+    pub hud: HudConfig,
+    pub pause: PauseConfig,
+    pub sound: SoundConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadConfig {
+    //Chip-8 keys 0-F to the gilrs buttons that press them
+    pub keys: BTreeMap<String, Vec<Button>>,
+    pub hotkeys: BTreeMap<Hotkey, Vec<Button>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeypadConfig {
+    //Virtual keypad drawn next to the screen for mouse and touch input
+    pub show: Option<bool>,
+    pub position: Option<KeypadPosition>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub scale: Option<ScaleMode>,
+    //Outlines every Chip-8 pixel
+    pub grid: Option<bool>,
+    //Degrees clockwise, 0, 90, 180 or 270
+    pub rotation: Option<Rotation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaletteConfig {
+    pub theme: Option<String>,
+    //Custom "#RRGGBB" colors, background first, these win over the theme
+    pub colors: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EffectsConfig {
+    pub persistence: Option<bool>,
+    pub decay: Option<f32>,
+    pub crt: Option<bool>,
+    pub scanlines: Option<f32>,
+    pub curvature: Option<f32>,
+    pub bloom: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadersConfig {
+    pub dir: Option<PathBuf>,
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenshotsConfig {
+    pub dir: Option<PathBuf>,
+    //Size of each Chip-8 pixel in the colored copy
+    pub scale: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordingConfig {
+    pub dir: Option<PathBuf>,
+    pub scale: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HudConfig {
+    //Frames and instructions per second in the corner
+    pub stats: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PauseConfig {
+    //Pauses while another window has focus and resumes on coming back
+    pub on_focus_loss: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundConfig {
+    //From 0 to 1
+    pub volume: Option<f32>,
+    pub muted: Option<bool>,
+    //Pitch of the buzzer in Hz
+    pub frequency: Option<f32>,
+    pub waveform: Option<Waveform>,
+    //Share of each period a pulse wave is high, from 0 to 1
+    pub duty: Option<f32>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read config {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    //Missing files are not an error, they just leave the defaults alone
+    pub fn load_if_exists(path: &Path) -> Result<Option<Config>, Error> {
+        match path.exists() {
+            true => Self::load(path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    //Roms can ship their own settings in a toml file next to them
+    pub fn rom_config_path(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("toml")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_take_several_inputs() {
+        let config: Config = toml::from_str(
+            r#"
+            [keys]
+            5 = ["KeyW", "ArrowUp"]
+            [hotkeys]
+            pause = ["KeyP", "Pause"]
+            [gamepad.keys]
+            5 = ["DPadUp", "North"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.keys["5"], vec![KeyCode::KeyW, KeyCode::ArrowUp]);
+        assert_eq!(config.hotkeys[&Hotkey::Pause], vec![KeyCode::KeyP, KeyCode::Pause]);
+        assert_eq!(config.gamepad.keys["5"], vec![Button::DPadUp, Button::North]);
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(toml::from_str::<Config>("[keys]\n5 = [\"NotAKey\"]").is_err());
+        assert!(toml::from_str::<Config>("[display]\nzoom = 2").is_err());
+    }
+
+    #[test]
+    fn rom_config_sits_next_to_the_rom() {
+        assert_eq!(Config::rom_config_path(Path::new("roms/pong.ch8")), Path::new("roms/pong.toml"));
+    }
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+use anyhow::Error;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    chip8::quirks::{Platform, Quirks},
+    layout::Rotation,
+};
+
+//Same layout as programs.json from the community chip-8-database
+const BUNDLED_DATABASE: &str = include_str!("../database/programs.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<RomColors>,
+    screen_rotation: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RomColors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub tick_rate: Option<u32>,
+    //Semantic key names ("up", "a", ...) mapped to Chip-8 keys
+    pub keys: HashMap<String, u8>,
+    //"#RRGGBB" colors for each combination of planes
+    pub colors: Option<Vec<String>>,
+    pub rotation: Option<Rotation>,
+}
+
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_DATABASE).expect("Bundled rom database is malformed")
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(json: &str) -> Result<Self, Error> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+        let roms = programs
+            .into_iter()
+            .flat_map(|program| {
+                let title = program.title;
+                program.roms.into_iter().filter_map(move |(hash, rom)| {
+                    let (id, platform) = rom
+                        .platforms
+                        .iter()
+                        .find_map(|id| Platform::from_database_id(id).map(|p| (id, p)))?;
+                    let mut quirks = platform.quirks();
+                    if let Some(overrides) = rom.quirky_platforms.get(id) {
+                        //Quirks this emulator does not implement are ignored
+                        overrides.iter().for_each(|(name, &value)| {
+                            let _ = quirks.set(name, value);
+                        });
+                    }
+                    let info = RomInfo {
+                        title: title.clone(),
+                        platform,
+                        quirks,
+                        tick_rate: rom.tickrate,
+                        keys: rom.keys,
+                        colors: rom.colors.map(|colors| colors.pixels),
+                        rotation: rom.screen_rotation.and_then(|degrees| Rotation::try_from(degrees).ok()),
+                    };
+                    Some((hash.to_ascii_lowercase(), info))
+                })
+            })
+            .collect();
+        Ok(Self { roms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&sha1_hex(rom))
+    }
+}
+
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    Sha1::digest(bytes)
+        .iter()
+        .fold(String::with_capacity(40), |mut acc, b| {
+            write!(acc, "{:02x}", b).unwrap();
+            acc
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &[u8] = &[0x00, 0xE0, 0x12, 0x00];
+
+    fn database() -> RomDatabase {
+        let json = format!(
+            r##"[
+                {{
+                    "title": "Test",
+                    "roms": {{
+                        "{}": {{
+                            "platforms": ["unknownPlatform", "superchip"],
+                            "quirkyPlatforms": {{ "superchip": {{ "wrap": true, "fontStyle": true }} }},
+                            "tickrate": 42,
+                            "keys": {{ "up": 5 }},
+                            "colors": {{ "pixels": ["#000000", "#ffffff"] }},
+                            "screenRotation": 90
+                        }},
+                        "0000000000000000000000000000000000000000": {{ "platforms": ["megachip9"] }}
+                    }}
+                }},
+                {{ "title": "No roms" }}
+            ]"##,
+            sha1_hex(ROM).to_ascii_uppercase()
+        );
+        RomDatabase::parse(&json).unwrap()
+    }
+
+    #[test]
+    fn lookup_finds_roms_by_hash() {
+        let database = database();
+        let info = database.lookup(ROM).unwrap();
+        assert_eq!(info.title, "Test");
+        assert_eq!(info.tick_rate, Some(42));
+        assert_eq!(info.keys.get("up"), Some(&5));
+        assert_eq!(info.colors.as_deref(), Some(&["#000000".to_owned(), "#ffffff".to_owned()][..]));
+        assert_eq!(info.rotation, Some(Rotation::Clockwise));
+        assert!(database.lookup(&[0x12, 0x00]).is_none());
+    }
+
+    #[test]
+    fn first_known_platform_and_its_quirks_are_used() {
+        let info = database().lookup(ROM).cloned().unwrap();
+        assert_eq!(info.platform, Platform::SuperChip);
+        let mut quirks = Platform::SuperChip.quirks();
+        quirks.wrap = true;
+        assert_eq!(info.quirks, quirks);
+    }
+
+    #[test]
+    fn roms_without_a_known_platform_are_left_out() {
+        assert_eq!(database().roms.len(), 1);
+    }
+
+    #[test]
+    fn malformed_json_is_an_error() {
+        assert!(RomDatabase::parse("{").is_err());
+        assert!(RomDatabase::parse(r#"[{ "roms": {} }]"#).is_err());
+    }
+
+    #[test]
+    fn bundled_database_parses() {
+        RomDatabase::bundled();
+    }
+
+    #[test]
+    fn sha1_is_lowercase_hex() {
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+}
@@ -0,0 +1,49 @@
+use bytemuck::{Pod, Zeroable};
+
+//Post-processing applied to the screen, each effect can be toggled while running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Effects {
+    //Blends each frame with the previous ones so XOR-redrawn sprites stop flickering
+    pub persistence: bool,
+    //Share of a pixel's brightness that is kept each frame
+    pub decay: f32,
+    pub crt: bool,
+    pub scanlines: f32,
+    pub curvature: f32,
+    pub bloom: f32,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            persistence: false,
+            decay: 0.6,
+            crt: false,
+            scanlines: 0.35,
+            curvature: 0.08,
+            bloom: 0.25,
+        }
+    }
+}
+
+impl Effects {
+    //Strengths as the shaders see them, disabled effects are zeroed
+    pub fn uniform(&self) -> EffectsUniform {
+        let crt = |strength: f32| if self.crt { strength.clamp(0.0, 1.0) } else { 0.0 };
+        EffectsUniform {
+            decay: if self.persistence { self.decay.clamp(0.0, 0.99) } else { 0.0 },
+            scanlines: crt(self.scanlines),
+            curvature: crt(self.curvature),
+            bloom: crt(self.bloom),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct EffectsUniform {
+    pub decay: f32,
+    pub scanlines: f32,
+    pub curvature: f32,
+    pub bloom: f32,
+}
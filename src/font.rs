@@ -0,0 +1,143 @@
+use crate::layout::{Quad, Rect};
+
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+//Glyphs plus the gap after them, in font pixels
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+//3x5 glyphs for ' ' through '_', one row per byte with the leftmost pixel in bit 2
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 64] = [
+    [0, 0, 0, 0, 0], // ' '
+    [2, 2, 2, 0, 2], // !
+    [5, 5, 0, 0, 0], // "
+    [5, 7, 5, 7, 5], // #
+    [3, 6, 7, 3, 6], // $
+    [5, 1, 2, 4, 5], // %
+    [2, 5, 2, 5, 3], // &
+    [2, 2, 0, 0, 0], // '
+    [1, 2, 2, 2, 1], // (
+    [4, 2, 2, 2, 4], // )
+    [0, 5, 2, 5, 0], // *
+    [0, 2, 7, 2, 0], // +
+    [0, 0, 0, 2, 4], // ,
+    [0, 0, 7, 0, 0], // -
+    [0, 0, 0, 0, 2], // .
+    [1, 1, 2, 4, 4], // /
+    [7, 5, 5, 5, 7], // 0
+    [2, 6, 2, 2, 7], // 1
+    [7, 1, 7, 4, 7], // 2
+    [7, 1, 7, 1, 7], // 3
+    [5, 5, 7, 1, 1], // 4
+    [7, 4, 7, 1, 7], // 5
+    [7, 4, 7, 5, 7], // 6
+    [7, 1, 1, 1, 1], // 7
+    [7, 5, 7, 5, 7], // 8
+    [7, 5, 7, 1, 7], // 9
+    [0, 2, 0, 2, 0], // :
+    [0, 2, 0, 2, 4], // ;
+    [1, 2, 4, 2, 1], // <
+    [0, 7, 0, 7, 0], // =
+    [4, 2, 1, 2, 4], // >
+    [7, 1, 3, 0, 2], // ?
+    [2, 5, 7, 4, 3], // @
+    [2, 5, 7, 5, 5], // A
+    [6, 5, 6, 5, 6], // B
+    [3, 4, 4, 4, 3], // C
+    [6, 5, 5, 5, 6], // D
+    [7, 4, 6, 4, 7], // E
+    [7, 4, 6, 4, 4], // F
+    [3, 4, 5, 5, 3], // G
+    [5, 5, 7, 5, 5], // H
+    [7, 2, 2, 2, 7], // I
+    [1, 1, 1, 5, 2], // J
+    [5, 5, 6, 5, 5], // K
+    [4, 4, 4, 4, 7], // L
+    [5, 7, 7, 5, 5], // M
+    [6, 5, 5, 5, 5], // N
+    [2, 5, 5, 5, 2], // O
+    [6, 5, 6, 4, 4], // P
+    [2, 5, 5, 6, 3], // Q
+    [6, 5, 6, 5, 5], // R
+    [3, 4, 2, 1, 6], // S
+    [7, 2, 2, 2, 2], // T
+    [5, 5, 5, 5, 7], // U
+    [5, 5, 5, 5, 2], // V
+    [5, 5, 7, 7, 5], // W
+    [5, 5, 2, 5, 5], // X
+    [5, 5, 2, 2, 2], // Y
+    [7, 1, 2, 4, 7], // Z
+    [6, 4, 4, 4, 6], // [
+    [4, 4, 2, 1, 1], // \
+    [3, 1, 1, 1, 3], // ]
+    [2, 5, 0, 0, 0], // ^
+    [0, 0, 0, 0, 7], // _
+];
+
+//Lowercase is drawn as uppercase and anything else missing as a question mark
+pub fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = match c.to_ascii_uppercase() {
+        '`' => '\'',
+        '{' => '(',
+        '}' => ')',
+        '|' => return [2; GLYPH_HEIGHT],
+        '~' => '-',
+        '\t' => ' ',
+        c => c,
+    };
+    match c {
+        ' '..='_' => GLYPHS[c as usize - ' ' as usize],
+        _ => GLYPHS['?' as usize - ' ' as usize],
+    }
+}
+
+//Quads for a single line of text with its top left corner at x, y
+pub fn text(line: &str, x: f32, y: f32, pixel: f32, color: [f32; 4]) -> Vec<Quad> {
+    let mut quads = Vec::new();
+    for (i, c) in line.chars().enumerate() {
+        let left = x + (i * ADVANCE) as f32 * pixel;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (4 >> col) != 0 {
+                    quads.push(Quad {
+                        rect: Rect::new(left + col as f32 * pixel, y + row as f32 * pixel, pixel, pixel),
+                        color,
+                    });
+                }
+            }
+        }
+    }
+    quads
+}
+
+//Breaks text into lines of at most the given number of characters
+pub fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let columns = columns.max(1);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let chars: Vec<char> = line.trim_end().chars().collect();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        for chunk in chars.chunks(columns) {
+            lines.push(chunk.iter().collect());
+        }
+    }
+    lines
+}
+
+//Text on a dark backdrop filling the rect, cut off when it does not fit
+pub fn text_box(rect: Rect, message: &str, pixel: f32, color: [f32; 4]) -> Vec<Quad> {
+    let mut quads = vec![Quad {
+        rect,
+        color: [0.0, 0.0, 0.0, 0.85],
+    }];
+    let margin = pixel * 2.0;
+    let columns = ((rect.w - margin * 2.0) / (ADVANCE as f32 * pixel)) as usize;
+    let rows = ((rect.h - margin * 2.0) / (LINE_HEIGHT as f32 * pixel)) as usize;
+    for (i, line) in wrap(message, columns).iter().take(rows).enumerate() {
+        let y = rect.y + margin + (i * LINE_HEIGHT) as f32 * pixel;
+        quads.extend(text(line, rect.x + margin, y, pixel, color));
+    }
+    quads
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use gilrs::{Button, EventType, Gilrs};
+
+use crate::bindings::{Action, Bindings, Held, Hotkey};
+
+const DEFAULT_KEYPAD: [(Button, u8); 10] = [
+    //Same keys as WASD on the default keyboard layout
+    (Button::DPadUp, 0x5),
+    (Button::DPadLeft, 0x7),
+    (Button::DPadDown, 0x8),
+    (Button::DPadRight, 0x9),
+    (Button::South, 0x6),
+    (Button::East, 0x4),
+    (Button::West, 0xE),
+    (Button::North, 0xD),
+    (Button::LeftTrigger, 0x1),
+    (Button::RightTrigger, 0xC),
+];
+
+const DEFAULT_HOTKEYS: [(Button, Hotkey); 2] = [
+    (Button::RightTrigger2, Hotkey::FastForward),
+    (Button::Select, Hotkey::Reset),
+];
+
+impl Default for Bindings<Button> {
+    fn default() -> Self {
+        Self::new(&DEFAULT_KEYPAD, &DEFAULT_HOTKEYS)
+    }
+}
+
+//Controller events stripped down to what the emulator needs, so mappings can be
+//driven without real hardware
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadEvent {
+    Connected(usize),
+    Disconnected(usize),
+    Pressed(usize, Button),
+    Released(usize, Button),
+}
+
+pub struct GamepadMapper {
+    bindings: Bindings<Button>,
+    //Buttons down on each pad, and what they press
+    pressed: HashMap<(usize, Button), Action>,
+    held: Held<(usize, Button)>,
+}
+
+impl GamepadMapper {
+    pub fn new(bindings: Bindings<Button>) -> Self {
+        Self {
+            bindings,
+            pressed: HashMap::new(),
+            held: Held::default(),
+        }
+    }
+
+    //Returns the actions to apply and whether they are pressed. Buttons on any pad that
+    //share an action hold it together
+    pub fn handle(&mut self, event: PadEvent) -> Vec<(Action, bool)> {
+        match event {
+            PadEvent::Pressed(id, button) => match self.bindings.get(button) {
+                Some(action) if !self.pressed.contains_key(&(id, button)) => {
+                    self.pressed.insert((id, button), action);
+                    self.change((id, button), action, true)
+                }
+                _ => vec![],
+            },
+            PadEvent::Released(id, button) => match self.pressed.remove(&(id, button)) {
+                Some(action) => self.change((id, button), action, false),
+                None => vec![],
+            },
+            //Unplugging a controller lets go of everything it was holding
+            PadEvent::Disconnected(id) => {
+                let mut released: Vec<_> = self
+                    .pressed
+                    .iter()
+                    .filter(|((pad, _), _)| *pad == id)
+                    .map(|(&input, &action)| (input, action))
+                    .collect();
+                released.sort_by_key(|&((_, button), _)| button as u32);
+                released
+                    .into_iter()
+                    .flat_map(|(input, action)| {
+                        self.pressed.remove(&input);
+                        self.change(input, action, false)
+                    })
+                    .collect()
+            }
+            PadEvent::Connected(_) => vec![],
+        }
+    }
+
+    fn change(&mut self, input: (usize, Button), action: Action, pressed: bool) -> Vec<(Action, bool)> {
+        match self.held.update(input, action, pressed) {
+            true => vec![(action, pressed)],
+            false => vec![],
+        }
+    }
+}
+
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    mapper: GamepadMapper,
+}
+
+impl Gamepads {
+    pub fn new(bindings: Bindings<Button>) -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                eprintln!("Gamepad support unavailable: {}", e);
+                None
+            }
+        };
+        Self {
+            gilrs,
+            mapper: GamepadMapper::new(bindings),
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<(Action, bool)> {
+        let Some(gilrs) = &mut self.gilrs else {
+            return vec![];
+        };
+        let mut actions = vec![];
+        while let Some(event) = gilrs.next_event() {
+            let id = usize::from(event.id);
+            let event = match event.event {
+                EventType::Connected => {
+                    println!("Gamepad connected: {}", gilrs.gamepad(event.id).name());
+                    PadEvent::Connected(id)
+                }
+                EventType::Disconnected => {
+                    println!("Gamepad disconnected: {}", gilrs.gamepad(event.id).name());
+                    PadEvent::Disconnected(id)
+                }
+                EventType::ButtonPressed(button, _) => PadEvent::Pressed(id, button),
+                EventType::ButtonReleased(button, _) => PadEvent::Released(id, button),
+                _ => continue,
+            };
+            actions.extend(self.mapper.handle(event));
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapper() -> GamepadMapper {
+        GamepadMapper::new(Bindings::default())
+    }
+
+    #[test]
+    fn press_and_release_map_to_the_keypad() {
+        let mut mapper = mapper();
+        assert_eq!(mapper.handle(PadEvent::Connected(0)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::DPadUp)), vec![(Action::Keypad(0x5), true)]);
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::DPadUp)), vec![(Action::Keypad(0x5), false)]);
+    }
+
+    #[test]
+    fn hotkeys_are_mapped() {
+        let mut mapper = mapper();
+        let fast_forward = Action::Hotkey(Hotkey::FastForward);
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::RightTrigger2)), vec![(fast_forward, true)]);
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::RightTrigger2)), vec![(fast_forward, false)]);
+    }
+
+    #[test]
+    fn duplicate_press_is_ignored() {
+        let mut mapper = mapper();
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::South)), vec![(Action::Keypad(0x6), true)]);
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::South)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::South)), vec![(Action::Keypad(0x6), false)]);
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::South)), vec![]);
+    }
+
+    #[test]
+    fn unbound_buttons_do_nothing() {
+        let mut mapper = mapper();
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::Mode)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::Mode)), vec![]);
+    }
+
+    #[test]
+    fn disconnect_releases_everything_the_pad_held() {
+        let mut mapper = mapper();
+        mapper.handle(PadEvent::Pressed(0, Button::DPadUp));
+        mapper.handle(PadEvent::Pressed(0, Button::South));
+        mapper.handle(PadEvent::Pressed(1, Button::East));
+        assert_eq!(
+            mapper.handle(PadEvent::Disconnected(0)),
+            vec![(Action::Keypad(0x6), false), (Action::Keypad(0x5), false)]
+        );
+        //The other pad keeps its button, and the first one has nothing left to release
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::DPadUp)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Released(1, Button::East)), vec![(Action::Keypad(0x4), false)]);
+    }
+
+    #[test]
+    fn buttons_sharing_a_key_hold_it_together() {
+        let mut mapper = GamepadMapper::new(Bindings::new(&[(Button::DPadUp, 0x5), (Button::North, 0x5)], &[]));
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::DPadUp)), vec![(Action::Keypad(0x5), true)]);
+        assert_eq!(mapper.handle(PadEvent::Pressed(0, Button::North)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Pressed(1, Button::DPadUp)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Released(0, Button::DPadUp)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Disconnected(0)), vec![]);
+        assert_eq!(mapper.handle(PadEvent::Released(1, Button::DPadUp)), vec![(Action::Keypad(0x5), false)]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Error};
+
+use crate::{
+    args::Args,
+    bindings::parse_keypad_key,
+    buzzer::{Edge, SAMPLES_PER_TICK},
+    chip8::Chip8,
+    profile::Profile,
+    recorder::AudioRecorder,
+    reference::{Divergence, Lockstep},
+    screenshot,
+};
+
+//Cxnn draws the same numbers every time, so a headless run always ends the same way
+const SEED: u64 = 0;
+
+//A Chip-8 key going down or up at the start of a frame, counted from 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScheduledKey {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+//"30+5,40-5" presses 5 at frame 30 and lets go at frame 40. A leading @ reads the
+//schedule from a file, where entries can also be split by whitespace and # starts a comment
+pub fn parse_schedule(text: &str) -> Result<Vec<ScheduledKey>, Error> {
+    let text = match text.strip_prefix('@') {
+        Some(path) => {
+            let text = fs::read_to_string(path).with_context(|| format!("Could not read key schedule {}", path))?;
+            text.lines().map(|line| line.split('#').next().unwrap_or("")).collect::<Vec<_>>().join("\n")
+        }
+        None => text.to_owned(),
+    };
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (frame, key, pressed) = match entry.split_once('+') {
+                Some((frame, key)) => (frame, key, true),
+                None => {
+                    let (frame, key) = entry
+                        .split_once('-')
+                        .ok_or_else(|| anyhow!("\"{}\" should be <frame>+<key> or <frame>-<key>", entry))?;
+                    (frame, key, false)
+                }
+            };
+            let frame = frame.parse().with_context(|| format!("\"{}\" is not a frame number", frame))?;
+            Ok(ScheduledKey {
+                frame,
+                key: parse_keypad_key(key)?,
+                pressed,
+            })
+        })
+        .collect()
+}
+
+//The core on its own, or checked against the reference interpreter after every instruction
+enum Runner {
+    Plain(Box<Chip8>),
+    Differential(Box<Lockstep>),
+}
+
+impl Runner {
+    fn cpu(&self) -> &Chip8 {
+        match self {
+            Runner::Plain(cpu) => cpu,
+            Runner::Differential(lockstep) => lockstep.cpu(),
+        }
+    }
+
+    fn set_key(&mut self, key: &ScheduledKey) -> Result<(), Divergence> {
+        match self {
+            Runner::Plain(cpu) => cpu.set_key(key.key, key.pressed),
+            Runner::Differential(lockstep) => lockstep.set_key(key.key, key.pressed)?,
+        }
+        Ok(())
+    }
+
+    //Returns whether the buzzer sounds for the frame. Like in the window, that is
+    //decided by the instructions, before the timer tick at its end
+    fn run_frame(&mut self, profile: &Profile) -> Result<bool, Divergence> {
+        let sounding = match self {
+            Runner::Plain(cpu) => {
+                cpu.run_instructions(profile.tick_rate, profile.quirks.vblank);
+                let sounding = cpu.sound_active();
+                cpu.update_timers();
+                sounding
+            }
+            Runner::Differential(lockstep) => {
+                lockstep.run_instructions(profile.tick_rate)?;
+                let sounding = lockstep.cpu().sound_active();
+                lockstep.update_timers()?;
+                sounding
+            }
+        };
+        Ok(sounding)
+    }
+}
+
+//Runs the rom for a fixed number of frames with no window, GPU or sound, then prints
+//the screen or saves it, recording the beeper with --wav. A CPU error stops the run after
+//its frame and is returned, as is the first difference from the reference interpreter
+pub fn run(args: &Args, profile: &Profile, rom: &[u8]) -> Result<(), Error> {
+    let frames = args.frames.ok_or_else(|| anyhow!("--headless needs --frames"))?;
+    let mut runner = match args.differential {
+        true => Runner::Differential(Box::new(Lockstep::new(rom, profile.quirks, SEED))),
+        false => Runner::Plain(Box::new(
+            Chip8::new().with_quirks(profile.quirks).with_rom(rom).with_seed(SEED),
+        )),
+    };
+    let mut keys = args.keys.clone();
+    keys.sort_by_key(|key| key.frame);
+    let mut keys = keys.iter().peekable();
+    let mut audio = match &args.wav {
+        Some(path) => Some(AudioRecorder::new(path, profile.sound, false)?),
+        None => None,
+    };
+
+    let mut frame = 0;
+    while frame < frames && runner.cpu().error().is_none() {
+        while let Some(key) = keys.next_if(|key| key.frame <= frame) {
+            runner.set_key(key).context("Chip8 and the reference interpreter disagree")?;
+        }
+        let sounding = runner
+            .run_frame(profile)
+            .context("Chip8 and the reference interpreter disagree")?;
+        if let Some(audio) = &mut audio {
+            let start = frame * SAMPLES_PER_TICK;
+            audio.push(start, &[Edge { at: start, on: sounding }])?;
+        }
+        frame += 1;
+    }
+    if let Some(audio) = audio {
+        audio.finish()?;
+    }
+    if args.differential {
+        eprintln!("Chip8 and the reference interpreter agreed for {} frames", frame);
+    }
+
+    let cpu = runner.cpu();
+    let buffer = cpu.get_display_buffer();
+    let buffer = buffer.borrow();
+    match &args.output {
+        Some(path) => save(&buffer, path)?,
+        None => print!("{}", screenshot::ascii(&buffer)),
+    }
+    match cpu.error() {
+        Some(e) => Err(anyhow!("{}", e).context(format!("Stopped in frame {}", frame - 1))),
+        None => Ok(()),
+    }
+}
+
+//Checked before running so a typo does not waste the run
+pub fn check_output(path: &Path) -> Result<(), Error> {
+    match is_png(path) || is_pbm(path) {
+        true => Ok(()),
+        false => Err(anyhow!("Can only save the screen as .png or .pbm, not {}", path.display())),
+    }
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("png"))
+}
+
+fn is_pbm(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pbm"))
+}
+
+fn save(buffer: &[u8], path: &Path) -> Result<(), Error> {
+    match is_png(path) {
+        true => screenshot::save_raw(buffer, path),
+        false => screenshot::save_pbm(buffer, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn key(frame: u64, key: u8, pressed: bool) -> ScheduledKey {
+        ScheduledKey { frame, key, pressed }
+    }
+
+    #[test]
+    fn schedule_has_presses_and_releases() {
+        assert_eq!(
+            parse_schedule("30+5,40-5").unwrap(),
+            vec![key(30, 0x5, true), key(40, 0x5, false)]
+        );
+        assert_eq!(
+            parse_schedule(" 0+a  2-A,,7+f ").unwrap(),
+            vec![key(0, 0xA, true), key(2, 0xA, false), key(7, 0xF, true)]
+        );
+        assert_eq!(parse_schedule("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn schedule_file_skips_comments() {
+        let path = env::temp_dir().join(format!("chip8-keys-{}.txt", process::id()));
+        fs::write(&path, "# start the game\n30+5 40-5 # jump\n\n100+1,# ignored-2\n").unwrap();
+        let schedule = parse_schedule(&format!("@{}", path.display()));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            schedule.unwrap(),
+            vec![key(30, 0x5, true), key(40, 0x5, false), key(100, 0x1, true)]
+        );
+    }
+
+    #[test]
+    fn bad_schedules_are_errors() {
+        for text in ["30*5", "30", "x+5", "-1+5", "30+", "30+G", "30+10", "@/no/such/file"] {
+            assert!(parse_schedule(text).is_err(), "{} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn only_png_and_pbm_are_saved() {
+        assert!(check_output(Path::new("end.png")).is_ok());
+        assert!(check_output(Path::new("end.PBM")).is_ok());
+        assert!(check_output(Path::new("end.gif")).is_err());
+        assert!(check_output(Path::new("end")).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    font::{self, ADVANCE, GLYPH_HEIGHT, LINE_HEIGHT},
+    layout::{Quad, Rect},
+};
+
+const TOAST_TIME: Duration = Duration::from_secs(2);
+//Toasts fade out over the end of their time
+const FADE_TIME: Duration = Duration::from_millis(400);
+const MAX_TOASTS: usize = 4;
+//How often the counters are worked out, shorter is jumpier
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const ERROR_COLOR: [f32; 4] = [1.0, 0.45, 0.45, 1.0];
+const BACKDROP_ALPHA: f32 = 0.7;
+
+struct Toast {
+    text: String,
+    color: [f32; 4],
+    shown: Instant,
+}
+
+//Text drawn over the game: short lived messages, speed counters and the pause indicator
+pub struct Hud {
+    toasts: VecDeque<Toast>,
+    pub show_stats: bool,
+    pub paused: bool,
+    //Counted since the stats were last worked out
+    frames: u32,
+    instructions: u64,
+    since: Instant,
+    fps: f32,
+    ips: f32,
+}
+
+impl Hud {
+    pub fn new(show_stats: bool) -> Self {
+        Self {
+            toasts: VecDeque::new(),
+            show_stats,
+            paused: false,
+            frames: 0,
+            instructions: 0,
+            since: Instant::now(),
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    pub fn toast(&mut self, text: impl Into<String>) {
+        self.push(text.into(), TEXT_COLOR);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(text.into(), ERROR_COLOR);
+    }
+
+    fn push(&mut self, text: String, color: [f32; 4]) {
+        //Repeating a message, like flipping a toggle twice, replaces it instead of stacking
+        self.toasts.retain(|toast| toast.text.split(':').next() != text.split(':').next());
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back(Toast {
+            text,
+            color,
+            shown: Instant::now(),
+        });
+    }
+
+    //Toasts fade out, so they need drawing every frame until the last is gone
+    pub fn animating(&self) -> bool {
+        !self.toasts.is_empty()
+    }
+
+    //Called once for every frame presented with the instructions it ran
+    pub fn frame(&mut self, instructions: u32) {
+        self.frames += 1;
+        self.instructions += instructions as u64;
+        let elapsed = self.since.elapsed();
+        if elapsed >= STATS_INTERVAL {
+            self.fps = self.frames as f32 / elapsed.as_secs_f32();
+            self.ips = self.instructions as f32 / elapsed.as_secs_f32();
+            self.frames = 0;
+            self.instructions = 0;
+            self.since = Instant::now();
+        }
+    }
+
+    pub fn quads(&mut self, window: Rect, screen: Rect) -> Vec<Quad> {
+        let pixel = (window.w.min(window.h) / 200.0).floor().max(2.0);
+        let margin = pixel * 2.0;
+        let mut quads = Vec::new();
+
+        if self.show_stats {
+            let lines = [format!("{:.0} FPS", self.fps), format!("{} IPS", si(self.ips))];
+            for (i, line) in lines.iter().enumerate() {
+                let y = window.y + margin + (i * LINE_HEIGHT) as f32 * pixel;
+                quads.extend(label(line, window.x + margin, y, pixel, TEXT_COLOR));
+            }
+        }
+
+        if self.paused {
+            let big = pixel * 2.0;
+            let text = "PAUSED";
+            let (w, h) = (text_width(text, big), GLYPH_HEIGHT as f32 * big);
+            let (x, y) = (screen.x + (screen.w - w) / 2.0, screen.y + (screen.h - h) / 2.0);
+            quads.extend(label(text, x.round(), y.round(), big, TEXT_COLOR));
+        }
+
+        let now = Instant::now();
+        self.toasts.retain(|toast| now - toast.shown < TOAST_TIME);
+        for (i, toast) in self.toasts.iter().rev().enumerate() {
+            let left = TOAST_TIME - (now - toast.shown);
+            let fade = (left.as_secs_f32() / FADE_TIME.as_secs_f32()).min(1.0);
+            let [r, g, b, a] = toast.color;
+            let y = window.y + window.h - margin - ((i + 1) * LINE_HEIGHT) as f32 * pixel;
+            quads.extend(label(&toast.text, window.x + margin, y, pixel, [r, g, b, a * fade]));
+        }
+        quads
+    }
+}
+
+fn text_width(text: &str, pixel: f32) -> f32 {
+    (text.chars().count() * ADVANCE).saturating_sub(1) as f32 * pixel
+}
+
+//A line of text on a dark backdrop that fades along with it
+fn label(text: &str, x: f32, y: f32, pixel: f32, color: [f32; 4]) -> Vec<Quad> {
+    let backdrop = Rect::new(
+        x - pixel,
+        y - pixel,
+        text_width(text, pixel) + pixel * 2.0,
+        (GLYPH_HEIGHT + 2) as f32 * pixel,
+    );
+    let mut quads = vec![Quad {
+        rect: backdrop,
+        color: [0.0, 0.0, 0.0, BACKDROP_ALPHA * color[3]],
+    }];
+    quads.extend(font::text(text, x, y, pixel, color));
+    quads
+}
+
+//Big numbers shortened with K and M so the counters stay narrow
+fn si(value: f32) -> String {
+    match value {
+        v if v >= 1e6 => format!("{:.1}M", v / 1e6),
+        v if v >= 1e3 => format!("{:.1}K", v / 1e3),
+        v => format!("{:.0}", v),
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use crate::{
+    chip8::{keyboard::KeypadState, TEXT_SPRITES},
+    layout::{Quad, Rect},
+};
+
+//COSMAC VIP keypad layout
+const KEYS: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+//Space between keys as a fraction of a key
+const GAP: f32 = 0.1;
+//Size of one font pixel as a fraction of a key
+const LABEL_PIXEL: f32 = 0.1;
+
+const KEY_COLOR: [f32; 4] = [0.02, 0.02, 0.02, 1.0];
+const POLLED_COLOR: [f32; 4] = [0.04, 0.08, 0.04, 1.0];
+const HELD_COLOR: [f32; 4] = [0.15, 0.3, 0.15, 1.0];
+const LABEL_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+
+//Touches are tracked by their winit id, the mouse by this one
+pub const MOUSE_POINTER: u64 = u64::MAX;
+
+//On-screen keypad for mouse and touch input
+pub struct VirtualKeypad {
+    pub visible: bool,
+    pointers: HashMap<u64, u8>,
+}
+
+impl VirtualKeypad {
+    pub fn new(visible: bool) -> Self {
+        Self {
+            visible,
+            pointers: HashMap::new(),
+        }
+    }
+
+    pub fn key_at(rect: Rect, x: f32, y: f32) -> Option<u8> {
+        if !rect.contains(x, y) {
+            return None;
+        }
+        let col = ((x - rect.x) / rect.w * 4.0) as usize;
+        let row = ((y - rect.y) / rect.h * 4.0) as usize;
+        KEYS.get(row)?.get(col).copied()
+    }
+
+    //Each of these returns the Chip-8 keys that changed state
+    pub fn pointer_down(&mut self, id: u64, rect: Rect, x: f32, y: f32) -> Vec<(u8, bool)> {
+        let mut changes = self.pointer_up(id);
+        if let Some(key) = Self::key_at(rect, x, y) {
+            if !self.pointers.values().any(|&k| k == key) {
+                changes.push((key, true));
+            }
+            self.pointers.insert(id, key);
+        }
+        changes
+    }
+
+    //Sliding a finger across the keypad moves the press along with it
+    pub fn pointer_moved(&mut self, id: u64, rect: Rect, x: f32, y: f32) -> Vec<(u8, bool)> {
+        match self.pointers.get(&id) {
+            Some(&key) if Self::key_at(rect, x, y) != Some(key) => self.pointer_down(id, rect, x, y),
+            _ => vec![],
+        }
+    }
+
+    pub fn pointer_up(&mut self, id: u64) -> Vec<(u8, bool)> {
+        match self.pointers.remove(&id) {
+            Some(key) if !self.pointers.values().any(|&k| k == key) => vec![(key, false)],
+            _ => vec![],
+        }
+    }
+
+    pub fn release_all(&mut self) -> Vec<(u8, bool)> {
+        let ids: Vec<_> = self.pointers.keys().copied().collect();
+        ids.into_iter().flat_map(|id| self.pointer_up(id)).collect()
+    }
+
+    pub fn quads(&self, rect: Rect, state: KeypadState) -> Vec<Quad> {
+        let cell = rect.w / 4.0;
+        let size = cell * (1.0 - GAP);
+        let pixel = size * LABEL_PIXEL;
+        let mut quads = Vec::new();
+        for (row, keys) in KEYS.iter().enumerate() {
+            for (col, &key) in keys.iter().enumerate() {
+                let x = rect.x + col as f32 * cell + (cell - size) / 2.0;
+                let y = rect.y + row as f32 * cell + (cell - size) / 2.0;
+                let color = match (state.held[key as usize], state.polled[key as usize]) {
+                    (true, _) => HELD_COLOR,
+                    (false, true) => POLLED_COLOR,
+                    (false, false) => KEY_COLOR,
+                };
+                quads.push(Quad {
+                    rect: Rect::new(x, y, size, size),
+                    color,
+                });
+
+                //Labels use the Chip-8's own 4x5 font
+                let (label_x, label_y) = (x + (size - pixel * 4.0) / 2.0, y + (size - pixel * 5.0) / 2.0);
+                for (py, bits) in TEXT_SPRITES[key as usize].iter().enumerate() {
+                    for px in 0..4 {
+                        if bits & (0x80 >> px) != 0 {
+                            quads.push(Quad {
+                                rect: Rect::new(
+                                    label_x + px as f32 * pixel,
+                                    label_y + py as f32 * pixel,
+                                    pixel,
+                                    pixel,
+                                ),
+                                color: LABEL_COLOR,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        quads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Keys 100 pixels square, so the one in column c and row r is centred on c * 100 + 50, r * 100 + 50
+    const RECT: Rect = Rect { x: 0.0, y: 0.0, w: 400.0, h: 400.0 };
+
+    #[test]
+    fn keys_are_found_by_position() {
+        assert_eq!(VirtualKeypad::key_at(RECT, 50.0, 50.0), Some(0x1));
+        assert_eq!(VirtualKeypad::key_at(RECT, 150.0, 350.0), Some(0x0));
+        assert_eq!(VirtualKeypad::key_at(RECT, 399.0, 399.0), Some(0xF));
+        assert_eq!(VirtualKeypad::key_at(RECT, 450.0, 50.0), None);
+    }
+
+    #[test]
+    fn shared_key_is_released_by_the_last_pointer() {
+        let mut keypad = VirtualKeypad::new(true);
+        assert_eq!(keypad.pointer_down(1, RECT, 150.0, 150.0), vec![(0x5, true)]);
+        assert_eq!(keypad.pointer_down(MOUSE_POINTER, RECT, 160.0, 140.0), vec![]);
+        assert_eq!(keypad.pointer_up(1), vec![]);
+        assert_eq!(keypad.pointer_up(MOUSE_POINTER), vec![(0x5, false)]);
+        assert_eq!(keypad.pointer_up(MOUSE_POINTER), vec![]);
+    }
+
+    #[test]
+    fn sliding_moves_the_press() {
+        let mut keypad = VirtualKeypad::new(true);
+        keypad.pointer_down(1, RECT, 50.0, 50.0);
+        assert_eq!(keypad.pointer_moved(1, RECT, 60.0, 60.0), vec![]);
+        assert_eq!(keypad.pointer_moved(1, RECT, 150.0, 50.0), vec![(0x1, false), (0x2, true)]);
+        //Off the keypad nothing is held any more
+        assert_eq!(keypad.pointer_moved(1, RECT, 500.0, 50.0), vec![(0x2, false)]);
+        assert_eq!(keypad.pointer_up(1), vec![]);
+    }
+
+    #[test]
+    fn sliding_off_a_shared_key_keeps_it_held() {
+        let mut keypad = VirtualKeypad::new(true);
+        keypad.pointer_down(1, RECT, 50.0, 50.0);
+        keypad.pointer_down(2, RECT, 50.0, 50.0);
+        assert_eq!(keypad.pointer_moved(2, RECT, 150.0, 50.0), vec![(0x2, true)]);
+        assert_eq!(keypad.pointer_up(1), vec![(0x1, false)]);
+    }
+
+    #[test]
+    fn release_all_lifts_every_key() {
+        let mut keypad = VirtualKeypad::new(true);
+        keypad.pointer_down(1, RECT, 50.0, 50.0);
+        keypad.pointer_down(2, RECT, 50.0, 50.0);
+        keypad.pointer_down(3, RECT, 350.0, 350.0);
+        let mut released = keypad.release_all();
+        released.sort();
+        assert_eq!(released, vec![(0x1, false), (0xF, false)]);
+        assert_eq!(keypad.pointer_up(3), vec![]);
+    }
+}
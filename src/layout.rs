@@ -0,0 +1,237 @@
+use std::fmt;
+
+use serde::Deserialize;
+use winit::dpi::PhysicalSize;
+
+use crate::{ASPECT_RATIO, HEIGHT, WIDTH};
+
+//Share of the window the virtual keypad may take up
+const KEYPAD_SHARE: f32 = 0.4;
+//Width of the pixel grid's lines as a fraction of a Chip-8 pixel
+const GRID_LINE: f32 = 0.12;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.w && y < self.y + self.h
+    }
+
+    //Largest rect of the given aspect ratio centered inside this one
+    pub fn fit(&self, aspect_ratio: f32) -> Rect {
+        if aspect_ratio > self.w / self.h {
+            let h = self.w / aspect_ratio;
+            Rect::new(self.x, self.y + (self.h - h) / 2.0, self.w, h)
+        } else {
+            let w = self.h * aspect_ratio;
+            Rect::new(self.x + (self.w - w) / 2.0, self.y, w, self.h)
+        }
+    }
+}
+
+//Solid colored rectangle in window pixels, drawn over the game
+#[derive(Clone, Copy, Debug)]
+pub struct Quad {
+    pub rect: Rect,
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeypadPosition {
+    //Beside the screen on wide windows, under it on tall ones
+    #[default]
+    Auto,
+    Right,
+    Bottom,
+}
+
+//How the 64x32 screen is fitted into its part of the window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    //Original 4:3 television aspect, pixels come out taller than they are wide
+    #[default]
+    Tv,
+    //As large as possible with square pixels
+    Fit,
+    //Square pixels a whole number of window pixels across, so they are all the same size
+    Integer,
+    //Fills the whole area, whatever shape it is
+    Stretch,
+}
+
+//Turns of the screen clockwise, for games made for a display on its side or upside down
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u32")]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise,
+    UpsideDown,
+    CounterClockwise,
+}
+
+impl TryFrom<u32> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u32) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Rotation::None),
+            90 => Ok(Rotation::Clockwise),
+            180 => Ok(Rotation::UpsideDown),
+            270 => Ok(Rotation::CounterClockwise),
+            _ => Err(format!("Rotation must be 0, 90, 180 or 270 degrees, not {}", degrees)),
+        }
+    }
+}
+
+impl Rotation {
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise => 90,
+            Rotation::UpsideDown => 180,
+            Rotation::CounterClockwise => 270,
+        }
+    }
+
+    //Turned on its side, so the width and height swap places
+    pub fn sideways(self) -> bool {
+        matches!(self, Rotation::Clockwise | Rotation::CounterClockwise)
+    }
+
+    //Chip-8 pixels across and down as the screen appears in the window
+    pub fn size(self) -> (usize, usize) {
+        match self.sideways() {
+            true => (HEIGHT, WIDTH),
+            false => (WIDTH, HEIGHT),
+        }
+    }
+
+    //Point on the Chip-8 screen shown at a point of the rotated screen, both from 0 to 1
+    pub fn texel(self, u: f32, v: f32) -> (f32, f32) {
+        match self {
+            Rotation::None => (u, v),
+            Rotation::Clockwise => (v, 1.0 - u),
+            Rotation::UpsideDown => (1.0 - u, 1.0 - v),
+            Rotation::CounterClockwise => (1.0 - v, u),
+        }
+    }
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 4] = [ScaleMode::Tv, ScaleMode::Fit, ScaleMode::Integer, ScaleMode::Stretch];
+
+    pub fn next(self) -> ScaleMode {
+        let i = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    fn place(self, area: Rect, rotation: Rotation) -> Rect {
+        let (width, height) = rotation.size();
+        let native = width as f32 / height as f32;
+        match self {
+            ScaleMode::Tv => area.fit(aspect_ratio(rotation)),
+            ScaleMode::Fit => area.fit(native),
+            ScaleMode::Integer => {
+                let scale = (area.w / width as f32).min(area.h / height as f32).floor().max(1.0);
+                let (w, h) = (width as f32 * scale, height as f32 * scale);
+                let x = (area.x + (area.w - w) / 2.0).round();
+                let y = (area.y + (area.h - h) / 2.0).round();
+                Rect::new(x, y, w, h)
+            }
+            ScaleMode::Stretch => area,
+        }
+    }
+}
+
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScaleMode::Tv => "tv",
+            ScaleMode::Fit => "fit",
+            ScaleMode::Integer => "integer",
+            ScaleMode::Stretch => "stretch",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+//The television's shape, standing up when the screen is on its side
+fn aspect_ratio(rotation: Rotation) -> f32 {
+    match rotation.sideways() {
+        true => 1.0 / ASPECT_RATIO,
+        false => ASPECT_RATIO,
+    }
+}
+
+//Lines between the Chip-8 pixels on the screen, like the gaps on an LCD
+pub fn grid(screen: Rect, rotation: Rotation, color: [f32; 4]) -> Vec<Quad> {
+    let (width, height) = rotation.size();
+    let (pixel_w, pixel_h) = (screen.w / width as f32, screen.h / height as f32);
+    let (line_w, line_h) = ((pixel_w * GRID_LINE).max(1.0), (pixel_h * GRID_LINE).max(1.0));
+    let columns = (1..width).map(|i| Quad {
+        rect: Rect::new(screen.x + i as f32 * pixel_w - line_w / 2.0, screen.y, line_w, screen.h),
+        color,
+    });
+    let rows = (1..height).map(|i| Quad {
+        rect: Rect::new(screen.x, screen.y + i as f32 * pixel_h - line_h / 2.0, screen.w, line_h),
+        color,
+    });
+    columns.chain(rows).collect()
+}
+
+//Where everything goes in a window of the given size
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Layout {
+    pub screen: Rect,
+    pub keypad: Option<Rect>,
+}
+
+impl Layout {
+    pub fn new(size: PhysicalSize<u32>, keypad: Option<KeypadPosition>, scale: ScaleMode, rotation: Rotation) -> Self {
+        let window = Rect::new(0.0, 0.0, size.width as f32, size.height as f32);
+        let Some(position) = keypad else {
+            return Self {
+                screen: scale.place(window, rotation),
+                keypad: None,
+            };
+        };
+        let position = match position {
+            KeypadPosition::Auto if window.w / window.h > aspect_ratio(rotation) => KeypadPosition::Right,
+            KeypadPosition::Auto => KeypadPosition::Bottom,
+            position => position,
+        };
+        let (screen, keypad) = match position {
+            KeypadPosition::Right => {
+                let side = window.h.min(window.w * KEYPAD_SHARE);
+                (
+                    Rect::new(0.0, 0.0, window.w - side, window.h),
+                    Rect::new(window.w - side, 0.0, side, window.h),
+                )
+            }
+            _ => {
+                let side = window.w.min(window.h * KEYPAD_SHARE);
+                (
+                    Rect::new(0.0, 0.0, window.w, window.h - side),
+                    Rect::new(0.0, window.h - side, window.w, side),
+                )
+            }
+        };
+        Self {
+            screen: scale.place(screen, rotation),
+            keypad: Some(keypad.fit(1.0)),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+#![feature(let_chains)]
+
+//The Chip-8 core on its own, with no window, GPU or sound, so tests and tools can drive it
+pub mod chip8;
+pub mod reference;
+//Benchmark roms, only built for the bench subcommand and the criterion benches
+#[cfg(feature = "bench")]
+pub mod workloads;
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
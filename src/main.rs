@@ -1,107 +1,53 @@
 #![feature(let_chains)]
-#![feature(get_many_mut)]
 
-use std::io::Cursor;
+use std::{io::Cursor, process};
 
+use app::App;
+use args::Args;
 use chip8::event::Chip8Event;
+#[cfg(feature = "bench")]
+use chip8_emulator::workloads;
+use chip8_emulator::{chip8, reference, HEIGHT, WIDTH};
 use chip8handler::Chip8Handler;
 use image::{codecs::ico::IcoDecoder, ImageDecoder};
-use render::Renderer;
+use layout::Rotation;
+use profile::Profile;
 use winit::{
-    event::{ElementState, Event, KeyEvent, WindowEvent},
     event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
-    keyboard::{KeyCode, PhysicalKey},
-    window::{Fullscreen, Icon, Window},
+    window::{Icon, Window},
 };
 
+mod analyzer;
+mod app;
 mod render;
 mod texture;
 mod chip8handler;
-mod chip8;
+mod args;
+#[cfg(feature = "bench")]
+mod bench;
+mod bindings;
+mod buzzer;
+mod config;
+mod database;
+mod effects;
+mod font;
+mod gamepad;
+mod headless;
+mod hud;
+mod keypad;
+mod layout;
+mod palette;
+mod preset;
+mod profile;
+mod recorder;
+mod screenshot;
+mod software;
 
 pub const ASPECT_RATIO: f32 = 4.0 / 3.0;
-pub const WIDTH: usize = 64;
-pub const HEIGHT: usize = 32;
 
-async fn execute_event_loop(event_loop: EventLoop<Chip8Event>, window: Window) {
-    let mut renderer = Renderer::new(&window).await;
-    let mut chip8 = Chip8Handler::new(event_loop.create_proxy());
-
-    event_loop.run(|event, event_target| match event {
-        Event::UserEvent(Chip8Event::RequestRedraw) => {
-            window.request_redraw();
-        }
-        Event::AboutToWait => {
-            window.request_redraw()
-        },
-        Event::WindowEvent { event, .. } => match event {
-            WindowEvent::CloseRequested => {
-                event_target.exit()
-            },
-            WindowEvent::Resized(new_size) => renderer.resize(new_size),
-            WindowEvent::RedrawRequested => {
-                chip8.update();
-                renderer.update_screen(&chip8.get_frame_buffer().borrow());
-                renderer.render()
-            },
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(keycode),
-                        state,
-                        repeat: false,
-                        ..
-                    },
-                ..
-            } => {
-                handle_chip8_input(state, keycode, &mut chip8);
-                if state == ElementState::Pressed && keycode == KeyCode::Enter {
-                    match window.fullscreen() {
-                        Some(_) => window.set_fullscreen(None),
-                        None => window.set_fullscreen(Some(Fullscreen::Borderless(None))),
-                    }
-                }
-                if keycode == KeyCode::Escape {
-                    event_target.exit();
-                }
-            },
-            _ => (),
-        },
-        _ => (),
-    }).unwrap();
-}
-
-fn handle_chip8_input(state: ElementState, keycode: KeyCode, chip8: &mut Chip8Handler) {
-    let state = match state {
-        ElementState::Pressed => true,
-        ElementState::Released => false,
-    };
-    match keycode {
-        KeyCode::Space => {
-            match state {
-                true => chip8.start_ff(),
-                false => chip8.stop_ff(),
-            }
-        },
-        KeyCode::Backslash => chip8.reset(),
-        KeyCode::Digit1 => chip8.update_key(0x1, state),
-        KeyCode::Digit2 => chip8.update_key(0x2, state),
-        KeyCode::Digit3 => chip8.update_key(0x3, state),
-        KeyCode::Digit4 => chip8.update_key(0xC, state),
-        KeyCode::KeyQ => chip8.update_key(0x4, state),
-        KeyCode::KeyW => chip8.update_key(0x5, state),
-        KeyCode::KeyE => chip8.update_key(0x6, state),
-        KeyCode::KeyR => chip8.update_key(0xD, state),
-        KeyCode::KeyA => chip8.update_key(0x7, state),
-        KeyCode::KeyS => chip8.update_key(0x8, state),
-        KeyCode::KeyD => chip8.update_key(0x9, state),
-        KeyCode::KeyF => chip8.update_key(0xE, state),
-        KeyCode::KeyZ => chip8.update_key(0xA, state),
-        KeyCode::KeyX => chip8.update_key(0x0, state),
-        KeyCode::KeyC => chip8.update_key(0xB, state),
-        KeyCode::KeyV => chip8.update_key(0xF, state),
-        _ => (),
-    }
+async fn execute_event_loop(event_loop: EventLoop<Chip8Event>, window: Window, args: Args, profile: Profile) {
+    let mut app = App::new(&window, event_loop.create_proxy(), args, profile).await;
+    event_loop.run(|event, event_target| app.handle_event(event, event_target)).unwrap();
 }
 
 const ICON: &[u8] = include_bytes!("../ch8.ico");
@@ -117,10 +63,40 @@ fn set_icon(window: &mut Window) {
 }
 
 pub fn main() {
+    let args = Args::from_env().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let rom = Chip8Handler::read_rom_from_fs(&args.rom_path);
+    let profile = Profile::resolve(&args, &rom).unwrap_or_else(|e| {
+        eprintln!("{:#}", e);
+        process::exit(1);
+    });
+    //Status goes to stderr, so a headless run prints nothing but the screen
+    eprintln!("Running as {} at {} instructions per frame", profile.platform, profile.tick_rate);
+    #[cfg(feature = "bench")]
+    if args.bench {
+        bench::run(&args, &profile, &rom);
+        return;
+    }
+    if args.headless {
+        if let Err(e) = headless::run(&args, &profile, &rom) {
+            eprintln!("{:#}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    if profile.rotation != Rotation::None {
+        println!("Screen rotated {} degrees clockwise", profile.rotation.degrees());
+    }
+
     let event_loop = EventLoopBuilder::<Chip8Event>::with_user_event().build().expect("Could not create event_loop");
     event_loop.set_control_flow(ControlFlow::Poll);
     let mut window = Window::new(&event_loop).expect("Could not create window");
-    window.set_title("Chip-8 Emulator");
+    match &profile.title {
+        Some(title) => window.set_title(&format!("Chip-8 Emulator - {}", title)),
+        None => window.set_title("Chip-8 Emulator"),
+    }
     set_icon(&mut window);
-    futures::executor::block_on(execute_event_loop(event_loop, window));
+    futures::executor::block_on(execute_event_loop(event_loop, window, args, profile));
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Error};
+
+//Background, first plane, second plane and both planes as sRGB hex colors
+const THEMES: [(&str, [&str; 4]); 5] = [
+    ("default", ["#101910", "#6C956C", "#3A503A", "#A8D8A8"]),
+    ("amber", ["#1A0F00", "#FFB000", "#7F5800", "#FFD870"]),
+    ("green_phosphor", ["#041004", "#33FF33", "#149914", "#B0FFB0"]),
+    ("lcd", ["#8F9F83", "#2A3326", "#5C6B52", "#11160F"]),
+    ("high_contrast", ["#000000", "#FFFFFF", "#808080", "#FFFF00"]),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::themes().swap_remove(0)
+    }
+}
+
+impl Palette {
+    pub fn themes() -> Vec<Palette> {
+        THEMES
+            .iter()
+            .map(|(name, colors)| Palette::from_hex(name, colors).unwrap())
+            .collect()
+    }
+
+    pub fn theme(name: &str) -> Result<Palette, Error> {
+        Self::themes()
+            .into_iter()
+            .find(|theme| theme.name == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = THEMES.iter().map(|(name, _)| *name).collect();
+                anyhow!("Unknown theme \"{}\", expected one of {}", name, names.join(", "))
+            })
+    }
+
+    //Two colors are enough for games that only use one plane
+    pub fn from_hex(name: &str, colors: &[impl AsRef<str>]) -> Result<Palette, Error> {
+        let colors = colors
+            .iter()
+            .map(|c| parse_hex(c.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let colors = match colors[..] {
+            [bg, fg] => [bg, fg, mix(bg, fg), fg],
+            [bg, fg, plane2] => [bg, fg, plane2, fg],
+            [bg, fg, plane2, both] => [bg, fg, plane2, both],
+            _ => return Err(anyhow!("Palettes need between 2 and 4 colors")),
+        };
+        Ok(Palette {
+            name: name.to_owned(),
+            colors,
+        })
+    }
+
+    //Colors as shader inputs, linearized when the target converts back to sRGB
+    pub fn to_rgba(&self, linear: bool) -> [[f32; 4]; 4] {
+        self.colors.map(|rgb| {
+            let [r, g, b] = rgb.map(|c| {
+                let c = c as f32 / 255.0;
+                match linear {
+                    true => srgb_to_linear(c),
+                    false => c,
+                }
+            });
+            [r, g, b, 1.0]
+        })
+    }
+}
+
+fn parse_hex(color: &str) -> Result<[u8; 3], Error> {
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("\"{}\" is not a #RRGGBB color", color));
+    }
+    let [_, r, g, b] = u32::from_str_radix(hex, 16)?.to_be_bytes();
+    Ok([r, g, b])
+}
+
+fn mix(a: [u8; 3], b: [u8; 3]) -> [u8; 3] {
+    [0, 1, 2].map(|i| ((a[i] as u16 + b[i] as u16) / 2) as u8)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Error};
+use serde::Deserialize;
+
+//Multi-pass presets are directories holding this manifest next to their shaders
+pub const MANIFEST: &str = "preset.toml";
+//Brightness of the Chip-8 screen after phosphor persistence, always available as an input
+pub const SCREEN_INPUT: &str = "screen";
+
+//A chain of WGSL passes replacing the built-in screen shader
+#[derive(Clone, Debug)]
+pub struct Preset {
+    pub name: String,
+    pub textures: Vec<TextureDecl>,
+    pub passes: Vec<Pass>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureDecl {
+    pub name: String,
+    //Multiple of the Chip-8's resolution, the screen area of the window when missing
+    pub scale: Option<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pass {
+    pub file: PathBuf,
+    pub source: String,
+    pub inputs: Vec<Input>,
+    //Index into the preset's textures, the window for the last pass
+    pub output: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Screen,
+    Texture(usize),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    textures: Vec<TextureDecl>,
+    passes: Vec<PassDecl>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PassDecl {
+    shader: PathBuf,
+    #[serde(default = "default_inputs")]
+    inputs: Vec<String>,
+    output: Option<String>,
+}
+
+fn default_inputs() -> Vec<String> {
+    vec![SCREEN_INPUT.to_owned()]
+}
+
+//Names of the presets in a directory, single .wgsl files and preset directories
+pub fn list(dir: &Path) -> Result<Vec<String>, Error> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Could not read shader directory {}", dir.display()))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = match path.join(MANIFEST).is_file() {
+            true => path.file_name(),
+            false if path.extension().is_some_and(|e| e == "wgsl") => path.file_stem(),
+            false => None,
+        };
+        if let Some(name) = name.and_then(|name| name.to_str()) {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn load(dir: &Path, name: &str) -> Result<Preset, Error> {
+    let preset_dir = dir.join(name);
+    if !preset_dir.join(MANIFEST).is_file() {
+        let file = dir.join(name).with_extension("wgsl");
+        return Ok(Preset {
+            name: name.to_owned(),
+            textures: vec![],
+            passes: vec![Pass {
+                source: read_shader(&file)?,
+                file,
+                inputs: vec![Input::Screen],
+                output: None,
+            }],
+        });
+    }
+
+    let manifest_path = preset_dir.join(MANIFEST);
+    let text = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Could not read {}", manifest_path.display()))?;
+    let manifest: Manifest =
+        toml::from_str(&text).with_context(|| format!("Invalid preset {}", manifest_path.display()))?;
+    if manifest.passes.is_empty() {
+        return Err(anyhow!("Preset {} has no passes", name));
+    }
+    for (i, texture) in manifest.textures.iter().enumerate() {
+        if texture.name == SCREEN_INPUT || manifest.textures[..i].iter().any(|t| t.name == texture.name) {
+            return Err(anyhow!("Texture name \"{}\" is already taken", texture.name));
+        }
+        if texture.scale.is_some_and(|scale| !(scale > 0.0 && scale <= 16.0)) {
+            return Err(anyhow!("Texture \"{}\" needs a scale between 0 and 16", texture.name));
+        }
+    }
+
+    //Passes may only read what earlier passes have written
+    let texture_index = |name: &str| manifest.textures.iter().position(|t| t.name == name);
+    let mut written = vec![false; manifest.textures.len()];
+    let mut passes = Vec::new();
+    let last = manifest.passes.len() - 1;
+    for (i, pass) in manifest.passes.into_iter().enumerate() {
+        let file = preset_dir.join(&pass.shader);
+        let inputs = pass
+            .inputs
+            .iter()
+            .map(|input| match input.as_str() {
+                SCREEN_INPUT => Ok(Input::Screen),
+                input => match texture_index(input) {
+                    Some(t) if written[t] => Ok(Input::Texture(t)),
+                    Some(_) => Err(anyhow!("{} reads \"{}\" before any pass writes it", pass.shader.display(), input)),
+                    None => Err(anyhow!("{} reads undeclared texture \"{}\"", pass.shader.display(), input)),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let output = match (&pass.output, i == last) {
+            (None, true) => None,
+            (Some(_), true) => return Err(anyhow!("The last pass draws to the window and cannot have an output")),
+            (None, false) => return Err(anyhow!("{} needs an output texture", pass.shader.display())),
+            (Some(output), false) => {
+                let t = texture_index(output)
+                    .ok_or_else(|| anyhow!("{} writes undeclared texture \"{}\"", pass.shader.display(), output))?;
+                if inputs.contains(&Input::Texture(t)) {
+                    return Err(anyhow!("{} cannot read and write \"{}\"", pass.shader.display(), output));
+                }
+                written[t] = true;
+                Some(t)
+            }
+        };
+        passes.push(Pass {
+            source: read_shader(&file)?,
+            file,
+            inputs,
+            output,
+        });
+    }
+    Ok(Preset {
+        name: name.to_owned(),
+        textures: manifest.textures,
+        passes,
+    })
+}
+
+fn read_shader(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).with_context(|| format!("Could not read shader {}", path.display()))
+}
@@ -0,0 +1,334 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Error};
+use gilrs::Button;
+use winit::keyboard::KeyCode;
+
+use crate::{
+    analyzer,
+    args::Args,
+    bindings::{Action, Bindings},
+    buzzer::{Sound, Tone},
+    chip8::quirks::{Platform, Quirks},
+    config::{Config, DEFAULT_CONFIG_PATH},
+    database::{sha1_hex, RomDatabase},
+    effects::Effects,
+    layout::{KeypadPosition, Rotation, ScaleMode},
+    palette::Palette,
+};
+
+//Everything about how a particular rom should be run
+pub struct Profile {
+    pub title: Option<String>,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub tick_rate: u32,
+    pub bindings: Bindings<KeyCode>,
+    pub gamepad_bindings: Bindings<Button>,
+    pub show_keypad: bool,
+    pub keypad_position: KeypadPosition,
+    pub scale: ScaleMode,
+    pub grid: bool,
+    pub rotation: Rotation,
+    //Themes in the order they are cycled through, starting with the active one
+    pub palettes: Vec<Palette>,
+    pub effects: Effects,
+    pub shader_dir: Option<PathBuf>,
+    //Preset to start with, the built-in shader when missing
+    pub shader: Option<String>,
+    pub screenshot_dir: PathBuf,
+    pub screenshot_scale: u32,
+    pub recording_dir: PathBuf,
+    pub recording_scale: u32,
+    pub show_stats: bool,
+    pub pause_on_focus_loss: bool,
+    pub sound: Sound,
+}
+
+impl Profile {
+    //Command line options take precedence over database entries, which take
+    //precedence over the platform guessed from the rom's opcodes
+    pub fn resolve(args: &Args, rom: &[u8]) -> Result<Profile, Error> {
+        let database = match &args.database {
+            Some(path) => RomDatabase::from_file(path)
+                .with_context(|| format!("Could not read rom database {}", path.display()))?,
+            None => RomDatabase::bundled(),
+        };
+        let info = database.lookup(rom);
+        match info {
+            Some(info) => eprintln!("Found \"{}\" in rom database ({})", info.title, info.platform),
+            None if database.is_empty() => eprintln!(
+                "Rom {} not looked up, the rom database is empty. Run database/fetch.sh and rebuild, or use --database",
+                sha1_hex(rom)
+            ),
+            None => eprintln!("Rom {} not found in database", sha1_hex(rom)),
+        }
+
+        let platform = match (args.platform, info) {
+            (Some(platform), _) => platform,
+            (None, Some(info)) => info.platform,
+            (None, None) => {
+                let analysis = analyzer::analyze(rom);
+                eprintln!("{}", analysis);
+                analysis.platform
+            }
+        };
+        let mut quirks = match (args.platform, info) {
+            (None, Some(info)) => info.quirks,
+            _ => platform.quirks(),
+        };
+        for (name, value) in &args.quirks {
+            quirks.set(name, *value)?;
+        }
+        //A database tick rate of 0 would never run an instruction, so it is left out
+        let tick_rate = args
+            .tick_rate
+            .or(info.and_then(|i| i.tick_rate).filter(|&tick_rate| tick_rate > 0))
+            .unwrap_or(platform.tick_rate());
+        let title = args.title.clone().or(info.map(|i| i.title.clone()));
+
+        //Key bindings layer defaults, database, user config and then the rom's own config
+        let mut bindings = Bindings::default();
+        let mut gamepad_bindings = Bindings::default();
+        if let Some(info) = info {
+            for (name, &key) in &info.keys {
+                let action = Action::Keypad(key & 0xF);
+                if let Some(code) = database_key(name) {
+                    bindings.add(code, action);
+                }
+                if let Some(button) = database_button(name) {
+                    gamepad_bindings.add(button, action);
+                }
+            }
+        }
+        let config = match &args.config {
+            Some(path) => Some(Config::load(path)?),
+            None => Config::load_if_exists(Path::new(DEFAULT_CONFIG_PATH))?,
+        };
+        let rom_config = Config::load_if_exists(&Config::rom_config_path(&args.rom_path))?;
+        let configs: Vec<&Config> = config.iter().chain(rom_config.iter()).collect();
+        for config in &configs {
+            bindings.apply(&config.keys, &config.hotkeys)?;
+            gamepad_bindings.apply(&config.gamepad.keys, &config.gamepad.hotkeys)?;
+        }
+        let show_keypad = layered(&configs, |c| c.keypad.show).unwrap_or(false);
+        let keypad_position = layered(&configs, |c| c.keypad.position).unwrap_or_default();
+        let scale = layered(&configs, |c| c.display.scale).unwrap_or_default();
+        let grid = layered(&configs, |c| c.display.grid).unwrap_or(false);
+        let rotation = args
+            .rotation
+            .or(layered(&configs, |c| c.display.rotation))
+            .or(info.and_then(|i| i.rotation))
+            .unwrap_or_default();
+
+        let mut palette = None;
+        for config in configs.iter().rev() {
+            if let Some(colors) = &config.palette.colors {
+                palette = Some(Palette::from_hex("custom", colors)?);
+                break;
+            }
+            if let Some(theme) = &config.palette.theme {
+                palette = Some(Palette::theme(theme)?);
+                break;
+            }
+        }
+        let palette = palette.unwrap_or_else(|| {
+            let colors = info.and_then(|i| i.colors.as_ref());
+            colors
+                .and_then(|colors| match Palette::from_hex("rom", &colors[..colors.len().min(4)]) {
+                    Ok(palette) => Some(palette),
+                    Err(e) => {
+                        eprintln!("Ignoring database colors: {}", e);
+                        None
+                    }
+                })
+                .unwrap_or_default()
+        });
+        let mut palettes = Palette::themes();
+        palettes.retain(|theme| *theme != palette);
+        palettes.insert(0, palette);
+
+        let defaults = Effects::default();
+        let effects = Effects {
+            persistence: layered(&configs, |c| c.effects.persistence).unwrap_or(defaults.persistence),
+            decay: layered(&configs, |c| c.effects.decay).unwrap_or(defaults.decay),
+            crt: layered(&configs, |c| c.effects.crt).unwrap_or(defaults.crt),
+            scanlines: layered(&configs, |c| c.effects.scanlines).unwrap_or(defaults.scanlines),
+            curvature: layered(&configs, |c| c.effects.curvature).unwrap_or(defaults.curvature),
+            bloom: layered(&configs, |c| c.effects.bloom).unwrap_or(defaults.bloom),
+        };
+
+        let shader_dir = args.shader_dir.clone().or(layered(&configs, |c| c.shaders.dir.clone()));
+        let shader = args.shader.clone().or(layered(&configs, |c| c.shaders.preset.clone()));
+        if shader.is_some() && shader_dir.is_none() {
+            return Err(anyhow!("Shader presets need a shader directory"));
+        }
+
+        let screenshot_dir = layered(&configs, |c| c.screenshots.dir.clone()).unwrap_or_else(|| PathBuf::from("."));
+        let screenshot_scale = layered(&configs, |c| c.screenshots.scale).unwrap_or(8).clamp(1, 64);
+        let recording_dir = layered(&configs, |c| c.recording.dir.clone()).unwrap_or_else(|| PathBuf::from("."));
+        //GIF frames are limited to 65535 pixels a side
+        let recording_scale = layered(&configs, |c| c.recording.scale).unwrap_or(4).clamp(1, 64);
+        let show_stats = layered(&configs, |c| c.hud.stats).unwrap_or(false);
+        let pause_on_focus_loss = layered(&configs, |c| c.pause.on_focus_loss).unwrap_or(false);
+
+        let defaults = Sound::default();
+        let sound = Sound {
+            tone: Tone {
+                waveform: layered(&configs, |c| c.sound.waveform).unwrap_or(defaults.tone.waveform),
+                frequency: layered(&configs, |c| c.sound.frequency)
+                    .unwrap_or(defaults.tone.frequency)
+                    .clamp(20.0, 20000.0),
+                duty: layered(&configs, |c| c.sound.duty).unwrap_or(defaults.tone.duty).clamp(0.01, 0.99),
+            },
+            volume: layered(&configs, |c| c.sound.volume).unwrap_or(defaults.volume).clamp(0.0, 1.0),
+            muted: layered(&configs, |c| c.sound.muted).unwrap_or(defaults.muted),
+        };
+
+        Ok(Profile {
+            title,
+            platform,
+            quirks,
+            tick_rate,
+            bindings,
+            gamepad_bindings,
+            show_keypad,
+            keypad_position,
+            scale,
+            grid,
+            rotation,
+            palettes,
+            effects,
+            shader_dir,
+            shader,
+            screenshot_dir,
+            screenshot_scale,
+            recording_dir,
+            recording_scale,
+            show_stats,
+            pause_on_focus_loss,
+            sound,
+        })
+    }
+}
+
+//The value from the last config that sets it
+fn layered<T>(configs: &[&Config], get: impl Fn(&Config) -> Option<T>) -> Option<T> {
+    configs.iter().rev().find_map(|config| get(config))
+}
+
+//Physical keys for the semantic names used in the database
+fn database_key(name: &str) -> Option<KeyCode> {
+    match name {
+        "up" => Some(KeyCode::ArrowUp),
+        "down" => Some(KeyCode::ArrowDown),
+        "left" => Some(KeyCode::ArrowLeft),
+        "right" => Some(KeyCode::ArrowRight),
+        "a" => Some(KeyCode::KeyK),
+        "b" => Some(KeyCode::KeyL),
+        _ => None,
+    }
+}
+
+fn database_button(name: &str) -> Option<Button> {
+    match name {
+        "up" => Some(Button::DPadUp),
+        "down" => Some(Button::DPadDown),
+        "left" => Some(Button::DPadLeft),
+        "right" => Some(Button::DPadRight),
+        "a" => Some(Button::South),
+        "b" => Some(Button::East),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    const ROM: &[u8] = &[0x00, 0xE0, 0x12, 0x00];
+
+    fn rotation(degrees: u32) -> Rotation {
+        Rotation::try_from(degrees).unwrap()
+    }
+
+    //Each layer is added on top of the last and has to win over everything before it
+    #[test]
+    fn layers_override_in_order() {
+        let dir = env::temp_dir().join(format!("chip8-profile-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            fs::write(&path, text).unwrap();
+            path
+        };
+        let entry = format!(
+            r#"[{{ "title": "Test", "roms": {{ "{}": {{ "platforms": ["superchip"], "tickrate": 42, "screenRotation": 90 }} }} }}]"#,
+            sha1_hex(ROM)
+        );
+        let mut args = Args {
+            rom_path: write("game.ch8", ""),
+            database: Some(write("empty.json", "[]")),
+            config: Some(write("empty.toml", "")),
+            ..Default::default()
+        };
+
+        let profile = Profile::resolve(&args, ROM).unwrap();
+        assert_eq!(
+            (profile.platform, profile.tick_rate, profile.rotation),
+            (Platform::Chip8, 15, Rotation::None)
+        );
+
+        args.database = Some(write("programs.json", &entry));
+        let profile = Profile::resolve(&args, ROM).unwrap();
+        assert_eq!(profile.title.as_deref(), Some("Test"));
+        assert_eq!(
+            (profile.platform, profile.tick_rate, profile.rotation),
+            (Platform::SuperChip, 42, rotation(90))
+        );
+
+        args.config = Some(write("chip8.toml", "[display]\nrotation = 180\n"));
+        assert_eq!(Profile::resolve(&args, ROM).unwrap().rotation, rotation(180));
+
+        write("game.toml", "[display]\nrotation = 270\n");
+        assert_eq!(Profile::resolve(&args, ROM).unwrap().rotation, rotation(270));
+
+        args.rotation = Some(Rotation::None);
+        args.tick_rate = Some(7);
+        args.platform = Some(Platform::Chip8);
+        let profile = Profile::resolve(&args, ROM).unwrap();
+        assert_eq!(
+            (profile.platform, profile.tick_rate, profile.rotation),
+            (Platform::Chip8, 7, Rotation::None)
+        );
+        assert_eq!(profile.quirks, Platform::Chip8.quirks());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn database_tickrate_of_zero_is_ignored() {
+        let dir = env::temp_dir().join(format!("chip8-tickrate-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let database = dir.join("programs.json");
+        let entry = format!(
+            r#"[{{ "title": "Test", "roms": {{ "{}": {{ "platforms": ["superchip"], "tickrate": 0 }} }} }}]"#,
+            sha1_hex(ROM)
+        );
+        fs::write(&database, entry).unwrap();
+        let config = dir.join("empty.toml");
+        fs::write(&config, "").unwrap();
+        let args = Args {
+            rom_path: dir.join("game.ch8"),
+            database: Some(database),
+            config: Some(config),
+            ..Default::default()
+        };
+        let profile = Profile::resolve(&args, ROM).unwrap();
+        assert_eq!(profile.tick_rate, Platform::SuperChip.tick_rate());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
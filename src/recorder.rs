@@ -0,0 +1,208 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Error};
+use gif::{Encoder, Frame, Repeat};
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::{
+    buzzer::{Edge, Oscillator, Sound, SAMPLES_PER_TICK, SAMPLE_RATE},
+    palette::Palette,
+    HEIGHT, WIDTH,
+};
+
+const FPS: u32 = 60;
+//Browsers slow anything shorter than this down to a tenth of a second
+const MIN_GIF_DELAY: f32 = 2.0;
+
+//Palette index of every pixel once each Chip-8 pixel is blown up to scale x scale
+fn scaled_pixels(buffer: &[u8], scale: usize) -> Vec<u8> {
+    let (w, h) = (WIDTH * scale, HEIGHT * scale);
+    (0..w * h)
+        .map(|i| {
+            let (x, y) = (i % w / scale, i / w / scale);
+            let bit = y * WIDTH + x;
+            (buffer[bit / 8] >> (7 - bit % 8)) & 1
+        })
+        .collect()
+}
+
+//Animated GIF of every frame that differs from the one before it
+pub struct GifRecorder {
+    encoder: Encoder<BufWriter<File>>,
+    scale: usize,
+    last: Option<Vec<u8>>,
+    //Emulator frames the last screen has been shown for
+    frames: u32,
+    //Centiseconds of screen time not handed to a GIF frame yet
+    carry: f32,
+}
+
+impl GifRecorder {
+    pub fn new(path: &Path, palette: &Palette, scale: usize) -> Result<Self, Error> {
+        let file = File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let [background, foreground, ..] = palette.colors;
+        let mut encoder = Encoder::new(
+            BufWriter::new(file),
+            (WIDTH * scale) as u16,
+            (HEIGHT * scale) as u16,
+            &[background, foreground].concat(),
+        )?;
+        encoder.set_repeat(Repeat::Infinite)?;
+        Ok(Self {
+            encoder,
+            scale,
+            last: None,
+            frames: 0,
+            carry: 0.0,
+        })
+    }
+
+    pub fn push(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        if self.last.as_deref() == Some(buffer) {
+            self.frames += 1;
+            return Ok(());
+        }
+        self.flush(false)?;
+        self.last = Some(buffer.to_vec());
+        self.frames = 1;
+        Ok(())
+    }
+
+    //Screens too short for a GIF frame are skipped and their time goes to the next one
+    fn flush(&mut self, finished: bool) -> Result<(), Error> {
+        let Some(last) = self.last.take() else {
+            return Ok(());
+        };
+        self.carry += self.frames as f32 * 100.0 / FPS as f32;
+        if self.carry < MIN_GIF_DELAY && !finished {
+            return Ok(());
+        }
+        let delay = self.carry.round().max(MIN_GIF_DELAY);
+        self.carry -= delay;
+        let frame = Frame {
+            width: (WIDTH * self.scale) as u16,
+            height: (HEIGHT * self.scale) as u16,
+            delay: delay as u16,
+            buffer: Cow::Owned(scaled_pixels(&last, self.scale)),
+            ..Frame::default()
+        };
+        self.encoder.write_frame(&frame)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.flush(true)?;
+        self.encoder.into_inner()?.flush()?;
+        Ok(())
+    }
+}
+
+//The beeper as a 16-bit mono WAV. Samples are worked out from emulated time
+//alone, so the same frames always give the same file, however fast they ran
+pub struct AudioRecorder {
+    writer: WavWriter<BufWriter<File>>,
+    oscillator: Oscillator,
+    volume: f32,
+    gate: bool,
+}
+
+impl AudioRecorder {
+    //The buzzer is recorded with its tone and volume, even while muted.
+    //Playing says whether it is sounding when the recording starts
+    pub fn new(path: &Path, sound: Sound, playing: bool) -> Result<Self, Error> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer =
+            WavWriter::create(path, spec).with_context(|| format!("Could not create {}", path.display()))?;
+        Ok(Self {
+            writer,
+            oscillator: Oscillator::new(sound.tone, SAMPLE_RATE),
+            volume: sound.volume,
+            gate: playing,
+        })
+    }
+
+    //One frame of samples, replaying the frame's edges from its point in emulated time
+    pub fn push(&mut self, start: u64, edges: &[Edge]) -> Result<(), Error> {
+        let mut edges = edges.iter().peekable();
+        for time in start..start + SAMPLES_PER_TICK {
+            while let Some(edge) = edges.next_if(|edge| edge.at <= time) {
+                self.gate = edge.on;
+            }
+            let sample = self.oscillator.next_sample(self.gate) * self.volume;
+            self.writer.write_sample((sample * i16::MAX as f32) as i16)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        self.writer.finalize()?;
+        Ok(())
+    }
+}
+
+//Lossless Y4M video with the beeper written to a WAV file beside it
+pub struct VideoRecorder {
+    video: BufWriter<File>,
+    audio: AudioRecorder,
+    scale: usize,
+    //Background and foreground as Y, Cb and Cr
+    colors: [[u8; 3]; 2],
+}
+
+impl VideoRecorder {
+    pub fn new(
+        video_path: &Path,
+        audio_path: &Path,
+        palette: &Palette,
+        scale: usize,
+        sound: Sound,
+        playing: bool,
+    ) -> Result<Self, Error> {
+        let file = File::create(video_path).with_context(|| format!("Could not create {}", video_path.display()))?;
+        let mut video = BufWriter::new(file);
+        writeln!(video, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", WIDTH * scale, HEIGHT * scale, FPS)?;
+        Ok(Self {
+            video,
+            audio: AudioRecorder::new(audio_path, sound, playing)?,
+            scale,
+            colors: [ycbcr(palette.colors[0]), ycbcr(palette.colors[1])],
+        })
+    }
+
+    //Every frame is written, so the video keeps a constant 60 fps
+    pub fn push(&mut self, buffer: &[u8], start: u64, edges: &[Edge]) -> Result<(), Error> {
+        let pixels = scaled_pixels(buffer, self.scale);
+        self.video.write_all(b"FRAME\n")?;
+        for plane in 0..3 {
+            let bytes: Vec<u8> = pixels.iter().map(|&p| self.colors[p as usize][plane]).collect();
+            self.video.write_all(&bytes)?;
+        }
+        self.audio.push(start, edges)
+    }
+
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.video.flush()?;
+        self.audio.finish()
+    }
+}
+
+//BT.601 studio range, what players assume for Y4M without a color range tag
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    [
+        16.0 + 0.2568 * r + 0.5041 * g + 0.0979 * b,
+        128.0 - 0.1482 * r - 0.2910 * g + 0.4392 * b,
+        128.0 + 0.4392 * r - 0.3678 * g - 0.0714 * b,
+    ]
+    .map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
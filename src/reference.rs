@@ -0,0 +1,535 @@
+//A second Chip-8, written as plainly as possible and kept apart from the real core so
+//changes to execute_instruction can be checked against it. Each opcode is one row of a
+//table and the screen is a grid of bools. Lockstep runs both an instruction at a time
+use std::fmt;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    chip8::{event::Chip8Event, quirks::Quirks, CPUError, Chip8, Snapshot, ENTRY_POINT, ERR_CODE, ERR_LOC, TEXT_SPRITES},
+    HEIGHT, WIDTH,
+};
+
+//Where the program counter goes after an instruction
+enum Next {
+    Step,
+    Skip,
+    Stay,
+    Jump(u16),
+    //Step, and the screen has changed
+    Draw,
+}
+
+//The fields of an opcode, named as in Cowgod's reference
+#[derive(Clone, Copy)]
+struct Fields {
+    x: usize,
+    y: usize,
+    n: u8,
+    nn: u8,
+    nnn: u16,
+}
+
+type Handler = fn(&mut Reference, Fields) -> Result<Next, CPUError>;
+
+//The first row whose mask and pattern match the opcode runs it, opcodes matching no row are unknown
+#[rustfmt::skip]
+const OPS: &[(u16, u16, Handler)] = &[
+    //Blank memory is a NOP that never moves on
+    (0xFFFF, 0x0000, |_, _| Ok(Next::Stay)),
+    (0xFFFF, 0x00E0, |c, _| {
+        c.screen = [[false; WIDTH]; HEIGHT];
+        Ok(Next::Step)
+    }),
+    (0xFFFF, 0x00EE, |c, _| {
+        let call = c.stack.pop().ok_or(CPUError::StackUnderflow)?;
+        Ok(Next::Jump(call + 2))
+    }),
+    (0xF000, 0x1000, |_, f| Ok(Next::Jump(f.nnn))),
+    (0xF000, 0x2000, |c, f| {
+        if c.stack.len() == 16 {
+            return Err(CPUError::StackOverflow);
+        }
+        c.stack.push(c.pc);
+        Ok(Next::Jump(f.nnn))
+    }),
+    (0xF000, 0x3000, |c, f| Ok(skip_if(c.v[f.x] == f.nn))),
+    (0xF000, 0x4000, |c, f| Ok(skip_if(c.v[f.x] != f.nn))),
+    (0xF00F, 0x5000, |c, f| Ok(skip_if(c.v[f.x] == c.v[f.y]))),
+    (0xF000, 0x6000, |c, f| {
+        c.v[f.x] = f.nn;
+        Ok(Next::Step)
+    }),
+    (0xF000, 0x7000, |c, f| {
+        c.v[f.x] = c.v[f.x].wrapping_add(f.nn);
+        Ok(Next::Step)
+    }),
+    (0xF00F, 0x8000, |c, f| {
+        c.v[f.x] = c.v[f.y];
+        Ok(Next::Step)
+    }),
+    (0xF00F, 0x8001, |c, f| c.logic(f, c.v[f.x] | c.v[f.y])),
+    (0xF00F, 0x8002, |c, f| c.logic(f, c.v[f.x] & c.v[f.y])),
+    (0xF00F, 0x8003, |c, f| c.logic(f, c.v[f.x] ^ c.v[f.y])),
+    (0xF00F, 0x8004, |c, f| {
+        let sum = c.v[f.x] as u16 + c.v[f.y] as u16;
+        c.with_flag(f, sum as u8, sum > 0xFF)
+    }),
+    (0xF00F, 0x8005, |c, f| {
+        let (vx, vy) = (c.v[f.x], c.v[f.y]);
+        c.with_flag(f, vx.wrapping_sub(vy), vx >= vy)
+    }),
+    (0xF00F, 0x8006, |c, f| {
+        let v = c.shifted(f);
+        c.with_flag(f, v >> 1, v & 0x01 == 1)
+    }),
+    (0xF00F, 0x8007, |c, f| {
+        let (vx, vy) = (c.v[f.x], c.v[f.y]);
+        c.with_flag(f, vy.wrapping_sub(vx), vy >= vx)
+    }),
+    (0xF00F, 0x800E, |c, f| {
+        let v = c.shifted(f);
+        c.with_flag(f, v << 1, v & 0x80 == 0x80)
+    }),
+    (0xF00F, 0x9000, |c, f| Ok(skip_if(c.v[f.x] != c.v[f.y]))),
+    (0xF000, 0xA000, |c, f| {
+        c.i = f.nnn;
+        Ok(Next::Step)
+    }),
+    (0xF000, 0xB000, |c, f| {
+        let offset = if c.quirks.jump { c.v[f.x] } else { c.v[0] };
+        Ok(Next::Jump(f.nnn + offset as u16))
+    }),
+    (0xF000, 0xC000, |c, f| {
+        c.v[f.x] = c.rng.gen_range(0x00..=0xFF_u8) & f.nn;
+        Ok(Next::Step)
+    }),
+    (0xF000, 0xD000, |c, f| c.draw(f)),
+    (0xF0FF, 0xE09E, |c, f| Ok(skip_if(c.keys[(c.v[f.x] & 0xF) as usize]))),
+    (0xF0FF, 0xE0A1, |c, f| Ok(skip_if(!c.keys[(c.v[f.x] & 0xF) as usize]))),
+    (0xF0FF, 0xF007, |c, f| {
+        c.v[f.x] = c.dt;
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF00A, |c, f| {
+        c.halted = Some(f.x);
+        c.st = 4;
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF015, |c, f| {
+        c.dt = c.v[f.x];
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF018, |c, f| {
+        c.st = c.v[f.x];
+        c.sound = c.st > 0;
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF01E, |c, f| {
+        c.i = c.i.wrapping_add(c.v[f.x] as u16);
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF029, |c, f| {
+        c.i = (c.v[f.x] & 0xF) as u16 * 5;
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF033, |c, f| {
+        let v = c.v[f.x];
+        c.store(c.i as usize, &[v / 100, v / 10 % 10, v % 10])?;
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF055, |c, f| {
+        let v = c.v;
+        c.store(c.i as usize, &v[..=f.x])?;
+        c.advance_i(f);
+        Ok(Next::Step)
+    }),
+    (0xF0FF, 0xF065, |c, f| {
+        let i = c.i as usize;
+        let bytes = c.ram.get(i..=i + f.x).ok_or(CPUError::RamOutOfBounds)?;
+        c.v[..=f.x].copy_from_slice(bytes);
+        c.advance_i(f);
+        Ok(Next::Step)
+    }),
+];
+
+fn skip_if(condition: bool) -> Next {
+    match condition {
+        true => Next::Skip,
+        false => Next::Step,
+    }
+}
+
+pub struct Reference {
+    ram: [u8; 0x1000],
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    stack: Vec<u16>,
+    dt: u8,
+    st: u8,
+    sound: bool,
+    keys: [bool; 16],
+    halted: Option<usize>,
+    error: Option<CPUError>,
+    screen: [[bool; WIDTH]; HEIGHT],
+    quirks: Quirks,
+    rng: StdRng,
+}
+
+impl Reference {
+    pub fn new(rom: &[u8], quirks: Quirks, seed: u64) -> Self {
+        let mut ram = [0; 0x1000];
+        for (i, &b) in TEXT_SPRITES.iter().flatten().enumerate() {
+            ram[i] = b;
+        }
+        for (i, &b) in ERR_CODE.iter().enumerate() {
+            ram[ERR_LOC as usize + i] = b;
+        }
+        for (i, &b) in rom.iter().enumerate().take(ram.len() - ENTRY_POINT as usize) {
+            ram[ENTRY_POINT as usize + i] = b;
+        }
+        Self {
+            ram,
+            v: [0; 16],
+            i: 0,
+            pc: ENTRY_POINT,
+            stack: Vec::new(),
+            dt: 0,
+            st: 0,
+            sound: false,
+            keys: [false; 16],
+            halted: None,
+            error: None,
+            screen: [[false; WIDTH]; HEIGHT],
+            quirks,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    //Runs one instruction and returns whether it drew
+    pub fn step(&mut self) -> bool {
+        let Some(op) = self.opcode() else {
+            self.fail(CPUError::RamOutOfBounds);
+            return false;
+        };
+        if self.halted.is_some() {
+            return false;
+        }
+        let fields = Fields {
+            x: (op >> 8 & 0xF) as usize,
+            y: (op >> 4 & 0xF) as usize,
+            n: (op & 0xF) as u8,
+            nn: (op & 0xFF) as u8,
+            nnn: op & 0xFFF,
+        };
+        let result = match OPS.iter().find(|(mask, pattern, _)| op & mask == *pattern) {
+            Some((_, _, run)) => run(self, fields),
+            None => Err(CPUError::UnknownOpcode(op, self.pc)),
+        };
+        match result {
+            Ok(Next::Step) => self.pc += 2,
+            Ok(Next::Skip) => self.pc += 4,
+            Ok(Next::Stay) => (),
+            Ok(Next::Jump(to)) => self.pc = to,
+            Ok(Next::Draw) => {
+                self.pc += 2;
+                return true;
+            }
+            Err(e) => self.fail(e),
+        }
+        false
+    }
+
+    pub fn update_timers(&mut self) {
+        if self.halted.is_none() {
+            self.dt = self.dt.saturating_sub(1);
+            self.st = self.st.saturating_sub(1);
+        }
+        if self.st == 0 {
+            self.sound = false;
+        }
+    }
+
+    //Fx0A beeps while a key is held and finishes once it is let go
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        let Some(held) = self.keys.get_mut(key as usize) else {
+            return;
+        };
+        *held = pressed;
+        if let Some(x) = self.halted {
+            self.sound = pressed;
+            if !pressed {
+                self.v[x] = key;
+                self.halted = None;
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let mut screen = vec![0; WIDTH / 8 * HEIGHT];
+        for (y, row) in self.screen.iter().enumerate() {
+            for (x, &lit) in row.iter().enumerate() {
+                if lit {
+                    screen[(y * WIDTH + x) / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        Snapshot {
+            pc: self.pc,
+            i: self.i,
+            reg: self.v,
+            stack: self.stack.clone(),
+            dt: self.dt,
+            st: self.st,
+            sound: self.sound,
+            halted: self.halted,
+            error: self.error,
+            ram: self.ram.to_vec(),
+            screen,
+        }
+    }
+
+    fn opcode(&self) -> Option<u16> {
+        let pc = self.pc as usize;
+        Some(u16::from_be_bytes([*self.ram.get(pc)?, *self.ram.get(pc + 1)?]))
+    }
+
+    //Like the core, the first error is kept and the program goes on from the error screen
+    fn fail(&mut self, e: CPUError) {
+        self.error = self.error.or(Some(e));
+        self.pc = ERR_LOC;
+    }
+
+    fn logic(&mut self, f: Fields, value: u8) -> Result<Next, CPUError> {
+        self.v[f.x] = value;
+        if self.quirks.logic {
+            self.v[0xF] = 0;
+        }
+        Ok(Next::Step)
+    }
+
+    //The flag is written last, so it wins when x is F
+    fn with_flag(&mut self, f: Fields, value: u8, flag: bool) -> Result<Next, CPUError> {
+        self.v[f.x] = value;
+        self.v[0xF] = flag as u8;
+        Ok(Next::Step)
+    }
+
+    fn shifted(&self, f: Fields) -> u8 {
+        match self.quirks.shift {
+            true => self.v[f.x],
+            false => self.v[f.y],
+        }
+    }
+
+    fn advance_i(&mut self, f: Fields) {
+        if !self.quirks.memory_leave_i {
+            self.i += f.x as u16 + 1;
+        }
+    }
+
+    //All or nothing, a store running past the end of RAM writes no bytes
+    fn store(&mut self, address: usize, bytes: &[u8]) -> Result<(), CPUError> {
+        let ram = self.ram.get_mut(address..address + bytes.len()).ok_or(CPUError::RamOutOfBounds)?;
+        ram.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    //Sprites start at Vx, Vy wrapped onto the screen. Pixels past the right or bottom edge
+    //wrap with the wrap quirk and are clipped without it
+    fn draw(&mut self, f: Fields) -> Result<Next, CPUError> {
+        let i = self.i as usize;
+        if i + f.n as usize > self.ram.len() {
+            return Err(CPUError::RamOutOfBounds);
+        }
+        let (left, top) = (self.v[f.x] as usize % WIDTH, self.v[f.y] as usize % HEIGHT);
+        let mut collision = false;
+        for row in 0..f.n as usize {
+            let sprite = self.ram[i + row];
+            for column in 0..8 {
+                let (mut x, mut y) = (left + column, top + row);
+                if self.quirks.wrap {
+                    (x, y) = (x % WIDTH, y % HEIGHT);
+                } else if x >= WIDTH || y >= HEIGHT {
+                    continue;
+                }
+                if sprite & (0x80 >> column) != 0 {
+                    collision |= self.screen[y][x];
+                    self.screen[y][x] = !self.screen[y][x];
+                }
+            }
+        }
+        self.v[0xF] = collision as u8;
+        Ok(Next::Draw)
+    }
+}
+
+//What was happening when the two interpreters first disagreed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Moment {
+    Instruction { pc: u16, opcode: u16 },
+    Timers,
+    Key { key: u8, pressed: bool },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub frame: u64,
+    pub moment: Moment,
+    pub difference: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Frame {}, ", self.frame)?;
+        match self.moment {
+            Moment::Instruction { pc, opcode } => write!(f, "0x{:04X} at 0x{:03X}", opcode, pc)?,
+            Moment::Timers => write!(f, "timer tick")?,
+            Moment::Key { key, pressed } => {
+                write!(f, "key {:X} {}", key, if pressed { "pressed" } else { "released" })?
+            }
+        }
+        write!(f, ": {}", self.difference)
+    }
+}
+impl std::error::Error for Divergence {}
+
+//The first difference between the core and the reference, described for a person
+pub fn compare(core: &Snapshot, reference: &Snapshot) -> Option<String> {
+    if core == reference {
+        return None;
+    }
+    let differs = |what: &str, core: String, reference: String| {
+        Some(format!("{} is {} in Chip8 but {} in the reference", what, core, reference))
+    };
+    if core.error != reference.error {
+        return differs("The error", format!("{:?}", core.error), format!("{:?}", reference.error));
+    }
+    if core.pc != reference.pc {
+        return differs("PC", format!("0x{:03X}", core.pc), format!("0x{:03X}", reference.pc));
+    }
+    if core.i != reference.i {
+        return differs("I", format!("0x{:03X}", core.i), format!("0x{:03X}", reference.i));
+    }
+    if let Some(r) = (0..16).find(|&r| core.reg[r] != reference.reg[r]) {
+        return differs(
+            &format!("V{:X}", r),
+            format!("0x{:02X}", core.reg[r]),
+            format!("0x{:02X}", reference.reg[r]),
+        );
+    }
+    if core.stack != reference.stack {
+        return differs("The stack", format!("{:03X?}", core.stack), format!("{:03X?}", reference.stack));
+    }
+    if (core.dt, core.st) != (reference.dt, reference.st) {
+        return differs(
+            "DT, ST",
+            format!("{}, {}", core.dt, core.st),
+            format!("{}, {}", reference.dt, reference.st),
+        );
+    }
+    if core.sound != reference.sound {
+        return differs("The buzzer", core.sound.to_string(), reference.sound.to_string());
+    }
+    if core.halted != reference.halted {
+        return differs("Fx0A", format!("{:?}", core.halted), format!("{:?}", reference.halted));
+    }
+    if let Some(a) = (0..core.ram.len()).find(|&a| core.ram[a] != reference.ram[a]) {
+        return differs(
+            &format!("RAM at 0x{:03X}", a),
+            format!("0x{:02X}", core.ram[a]),
+            format!("0x{:02X}", reference.ram[a]),
+        );
+    }
+    let lit = |screen: &[u8], x: usize, y: usize| screen[(y * WIDTH + x) / 8] & (0x80 >> (x % 8)) != 0;
+    for (y, x) in (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (y, x))) {
+        if lit(&core.screen, x, y) != lit(&reference.screen, x, y) {
+            let state = |lit| if lit { "on" } else { "off" }.to_owned();
+            return differs(
+                &format!("The pixel at {}, {}", x, y),
+                state(lit(&core.screen, x, y)),
+                state(lit(&reference.screen, x, y)),
+            );
+        }
+    }
+    None
+}
+
+//The core and the reference fed the same rom, quirks, random seed and keys, compared after every step
+pub struct Lockstep {
+    cpu: Chip8,
+    reference: Reference,
+    vblank: bool,
+    frame: u64,
+}
+
+impl Lockstep {
+    pub fn new(rom: &[u8], quirks: Quirks, seed: u64) -> Self {
+        Self {
+            cpu: Chip8::new().with_quirks(quirks).with_rom(rom).with_seed(seed),
+            reference: Reference::new(rom, quirks, seed),
+            vblank: quirks.vblank,
+            frame: 0,
+        }
+    }
+
+    pub fn cpu(&self) -> &Chip8 {
+        &self.cpu
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), Divergence> {
+        self.cpu.set_key(key, pressed);
+        self.reference.set_key(key, pressed);
+        self.check(Moment::Key { key, pressed }, None)
+    }
+
+    //Chip8::run_frame an instruction at a time
+    pub fn run_frame(&mut self, ipf: u32) -> Result<(), Divergence> {
+        self.run_instructions(ipf)?;
+        self.update_timers()
+    }
+
+    pub fn run_instructions(&mut self, ipf: u32) -> Result<(), Divergence> {
+        for _ in 0..ipf {
+            let moment = Moment::Instruction {
+                pc: self.reference.pc,
+                opcode: self.reference.opcode().unwrap_or(0),
+            };
+            let drew = self.cpu.update() == Some(Chip8Event::RequestRedraw);
+            let reference_drew = self.reference.step();
+            self.check(moment, (drew != reference_drew).then_some((drew, reference_drew)))?;
+            if drew && self.vblank {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    //Ends the frame
+    pub fn update_timers(&mut self) -> Result<(), Divergence> {
+        self.cpu.update_timers();
+        self.reference.update_timers();
+        self.check(Moment::Timers, None)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn check(&self, moment: Moment, drew: Option<(bool, bool)>) -> Result<(), Divergence> {
+        let difference = match drew {
+            Some((core, reference)) => Some(format!(
+                "Drawing is {} in Chip8 but {} in the reference",
+                core, reference
+            )),
+            None => compare(&self.cpu.snapshot(), &self.reference.snapshot()),
+        };
+        match difference {
+            Some(difference) => Err(Divergence {
+                frame: self.frame,
+                moment,
+                difference,
+            }),
+            None => Ok(()),
+        }
+    }
+}
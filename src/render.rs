@@ -1,13 +1,125 @@
+use anyhow::{anyhow, Context, Error};
 use bytemuck::{Pod, Zeroable};
 use std::borrow::Cow;
 use wgpu::{
-    util::DeviceExt, BindGroup, BindGroupLayout, Color, CommandEncoder, CommandEncoderDescriptor, Device, DeviceDescriptor, Features, FragmentState, FrontFace, IndexFormat, Instance, Limits, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor, PolygonMode, PowerPreference, PresentMode, PrimitiveState, PrimitiveTopology, Queue, RenderPass, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource, StoreOp, Surface, SurfaceConfiguration, TextureView, TextureViewDescriptor, VertexState
+    util::DeviceExt, BindGroup, BindGroupLayout, ErrorFilter, BlendState, Buffer, Color, ColorTargetState, ColorWrites, CommandEncoder, CommandEncoderDescriptor, Device, DeviceDescriptor, Features, FragmentState, FrontFace, IndexFormat, Instance, Limits, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor, PolygonMode, PowerPreference, PresentMode, PrimitiveState, PrimitiveTopology, Queue, RenderPass, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource, StoreOp, Surface, SurfaceConfiguration, TextureFormat, TextureView, TextureViewDescriptor, VertexState
 };
 use winit::{dpi::PhysicalSize, window::Window};
 
-use crate::{chip8::screen::Screen, texture::Texture, ASPECT_RATIO};
+use crate::{
+    effects::Effects,
+    layout::{KeypadPosition, Layout, Quad, Rect, Rotation, ScaleMode},
+    palette::Palette,
+    preset::{Input, Preset},
+    software::SoftwareRenderer,
+    texture::Texture,
+    HEIGHT, WIDTH,
+};
+
+const FRAME_LEN: usize = WIDTH / 8 * HEIGHT;
+
+//Draws with wgpu when there is a usable adapter and on the CPU otherwise
+pub enum Renderer<'a> {
+    Gpu(Box<GpuRenderer<'a>>),
+    Software(SoftwareRenderer<'a>),
+}
+
+impl<'a> Renderer<'a> {
+    pub async fn new(window: &'a Window, software: bool) -> Self {
+        if !software {
+            match GpuRenderer::new(window).await {
+                Ok(renderer) => return Renderer::Gpu(Box::new(renderer)),
+                Err(e) => eprintln!("GPU renderer unavailable, falling back to software rendering: {:#}", e),
+            }
+        }
+        Renderer::Software(SoftwareRenderer::new(window).expect("Could not create software renderer"))
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) -> Result<(), Error> {
+        match self {
+            Renderer::Gpu(r) => r.resize(new_size),
+            Renderer::Software(r) => {
+                r.resize(new_size);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        match self {
+            Renderer::Gpu(r) => r.layout(),
+            Renderer::Software(r) => r.layout(),
+        }
+    }
+
+    pub fn set_keypad(&mut self, keypad: Option<KeypadPosition>) -> Result<(), Error> {
+        match self {
+            Renderer::Gpu(r) => r.set_keypad(keypad),
+            Renderer::Software(r) => {
+                r.set_keypad(keypad);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: ScaleMode) -> Result<(), Error> {
+        match self {
+            Renderer::Gpu(r) => r.set_scale(scale),
+            Renderer::Software(r) => {
+                r.set_scale(scale);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) -> Result<(), Error> {
+        match self {
+            Renderer::Gpu(r) => r.set_rotation(rotation),
+            Renderer::Software(r) => {
+                r.set_rotation(rotation);
+                Ok(())
+            }
+        }
+    }
 
-pub struct Renderer<'a> {
+    pub fn set_palette(&mut self, palette: &Palette) {
+        match self {
+            Renderer::Gpu(r) => r.set_palette(palette),
+            Renderer::Software(r) => r.set_palette(palette),
+        }
+    }
+
+    pub fn set_effects(&mut self, effects: &Effects) {
+        match self {
+            Renderer::Gpu(r) => r.set_effects(effects),
+            Renderer::Software(r) => r.set_effects(effects),
+        }
+    }
+
+    pub fn set_preset(&mut self, preset: Option<Preset>) -> Result<(), Error> {
+        match self {
+            Renderer::Gpu(r) => r.set_preset(preset),
+            Renderer::Software(_) if preset.is_some() => Err(anyhow!("Shader presets need the GPU renderer")),
+            Renderer::Software(_) => Ok(()),
+        }
+    }
+
+    pub fn update_screen(&mut self, bytes: &[u8]) {
+        match self {
+            Renderer::Gpu(r) => r.update_screen(bytes),
+            Renderer::Software(r) => r.update_screen(bytes),
+        }
+    }
+
+    pub fn render(&mut self, overlay: &[Quad]) {
+        match self {
+            Renderer::Gpu(r) => r.render(overlay),
+            Renderer::Software(r) => r.render(overlay),
+        }
+    }
+}
+
+pub struct GpuRenderer<'a> {
     size: PhysicalSize<u32>,
     instance: Instance,
     surface: Surface<'a>,
@@ -15,14 +127,51 @@ pub struct Renderer<'a> {
     queue: Queue,
     config: SurfaceConfiguration,
     pipeline: RenderPipeline,
-    vertex_buffer: [Vertex; 4],
+    persistence_pipeline: RenderPipeline,
+    overlay_pipeline: RenderPipeline,
+    rotate_pipeline: RenderPipeline,
+    layout: Layout,
+    keypad: Option<KeypadPosition>,
+    scale: ScaleMode,
+    rotation: Rotation,
+    vertex_buffer: Buffer,
+    //Grown whenever a frame has more overlay vertices than fit
+    overlay_buffer: Buffer,
     diffuse_texture: Texture,
+    //Last screen uploaded, so only rows that changed are written again
+    frame: [u8; FRAME_LEN],
     texture_bind_group_layout: BindGroupLayout,
+    uniform_bind_group_layout: BindGroupLayout,
     diffuse_bind_group: BindGroup,
+    palette_buffer: Buffer,
+    palette_bind_group: BindGroup,
+    effects_buffer: Buffer,
+    effects_bind_group: BindGroup,
+    //Ping-ponged brightness of each pixel, the newest is at history_index
+    history: [Texture; 2],
+    history_bind_groups: [BindGroup; 2],
+    history_index: usize,
+    //User shader preset drawn instead of the built-in screen shader
+    preset: Option<Preset>,
+    chain: Option<ShaderChain>,
 }
 
-impl<'a> Renderer<'a> {
-    pub async fn new(window: &'a Window) -> Self {
+struct ShaderChain {
+    textures: Vec<Texture>,
+    passes: Vec<ChainPass>,
+    //The last pass draws upright into this when the screen is rotated
+    rotated: Option<(Texture, BindGroup)>,
+}
+
+struct ChainPass {
+    pipeline: RenderPipeline,
+    //Inputs bound for either history texture being the newest
+    bind_groups: [BindGroup; 2],
+    output: Option<usize>,
+}
+
+impl<'a> GpuRenderer<'a> {
+    pub async fn new(window: &'a Window) -> Result<Self, Error> {
         let mut size = window.inner_size();
         size.width = size.width.max(1);
         size.height = size.height.max(1);
@@ -30,7 +179,7 @@ impl<'a> Renderer<'a> {
         let instance = Instance::default();
         let surface = instance
             .create_surface(window)
-            .expect("Could not create surface");
+            .context("Could not create surface")?;
 
         let adapter = instance
             .request_adapter(&RequestAdapterOptions {
@@ -39,7 +188,7 @@ impl<'a> Renderer<'a> {
                 compatible_surface: Some(&surface),
             })
             .await
-            .expect("Could not find suitable adapter");
+            .context("Could not find suitable adapter")?;
 
         let (device, queue) = adapter
             .request_device(
@@ -52,15 +201,15 @@ impl<'a> Renderer<'a> {
                 None,
             )
             .await
-            .expect("Unable to request device from adapter");
+            .context("Unable to request device from adapter")?;
 
         let mut config = surface
             .get_default_config(&adapter, size.width, size.height)
-            .expect("No default config for surface");
+            .context("No default config for surface")?;
         config.present_mode = PresentMode::AutoVsync;
 
         surface.configure(&device, &config);
-        let diffuse_texture = Texture::from_bytes(&device, &queue, &Screen::default_buffer().borrow()).unwrap();
+        let diffuse_texture = Texture::screen(&device);
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -87,23 +236,66 @@ impl<'a> Renderer<'a> {
                 label: Some("texture_bind_group_layout"),
             });
 
-        let diffuse_bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                    }
-                ],
-                label: Some("diffuse_bind_group"),
-            }
+        let diffuse_bind_group = Self::texture_bind_group(
+            &device,
+            &texture_bind_group_layout,
+            &diffuse_texture,
+            "diffuse_bind_group",
         );
 
+        let history = [
+            Texture::render_target(&device, "history_texture", WIDTH as u32, HEIGHT as u32, TextureFormat::Rgba8Unorm),
+            Texture::render_target(&device, "history_texture", WIDTH as u32, HEIGHT as u32, TextureFormat::Rgba8Unorm),
+        ];
+        let history_bind_groups = [0, 1].map(|i| {
+            Self::texture_bind_group(&device, &texture_bind_group_layout, &history[i], "history_bind_group")
+        });
+
+        let palette_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("palette_buffer"),
+            contents: bytemuck::cast_slice(&Palette::default().to_rgba(config.format.is_srgb())),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("uniform_bind_group_layout"),
+            });
+
+        let palette_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: palette_buffer.as_entire_binding(),
+            }],
+            label: Some("palette_bind_group"),
+        });
+
+        let effects_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("effects_buffer"),
+            contents: bytemuck::bytes_of(&Effects::default().uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let effects_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: effects_buffer.as_entire_binding(),
+            }],
+            label: Some("effects_bind_group"),
+        });
+
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("screen_shader"),
             source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/shader.wgsl"))),
@@ -111,7 +303,11 @@ impl<'a> Renderer<'a> {
 
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("my_pipeline_layout"),
-            bind_group_layouts: &[&texture_bind_group_layout],
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &uniform_bind_group_layout,
+                &uniform_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
@@ -142,6 +338,124 @@ impl<'a> Renderer<'a> {
             multiview: None,
         });
 
+        let persistence_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("persistence_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/persistence.wgsl"))),
+        });
+
+        let persistence_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("persistence_pipeline_layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &texture_bind_group_layout,
+                &uniform_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let persistence_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("persistence_render_pipeline"),
+            layout: Some(&persistence_pipeline_layout),
+            vertex: VertexState {
+                module: &persistence_shader,
+                entry_point: "vertex",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &persistence_shader,
+                entry_point: "fragment",
+                targets: &[Some(TextureFormat::Rgba8Unorm.into())],
+            }),
+            primitive: PrimitiveState::default(),
+            multisample: MultisampleState::default(),
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        let overlay_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("overlay_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/overlay.wgsl"))),
+        });
+
+        let overlay_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("overlay_pipeline_layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let overlay_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("overlay_render_pipeline"),
+            layout: Some(&overlay_pipeline_layout),
+            vertex: VertexState {
+                module: &overlay_shader,
+                entry_point: "vertex",
+                buffers: &[OverlayVertex::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &overlay_shader,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format: config.format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            multisample: MultisampleState::default(),
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        let rotate_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("rotate_shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/rotate.wgsl"))),
+        });
+
+        let rotate_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("rotate_pipeline_layout"),
+            bind_group_layouts: &[&texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let rotate_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("rotate_render_pipeline"),
+            layout: Some(&rotate_pipeline_layout),
+            vertex: VertexState {
+                module: &rotate_shader,
+                entry_point: "vertex",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &rotate_shader,
+                entry_point: "fragment",
+                targets: &[Some(config.format.into())],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                strip_index_format: Some(IndexFormat::Uint32),
+                ..PrimitiveState::default()
+            },
+            multisample: MultisampleState::default(),
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Vertex Buffer"),
+            size: std::mem::size_of::<[Vertex; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let overlay_buffer = Self::create_overlay_buffer(&device, 0);
+
         let mut out = Self {
             size,
             instance,
@@ -150,18 +464,37 @@ impl<'a> Renderer<'a> {
             queue,
             config,
             pipeline,
-            vertex_buffer: [Vertex::default(); 4],
+            persistence_pipeline,
+            overlay_pipeline,
+            rotate_pipeline,
+            layout: Layout::default(),
+            keypad: None,
+            scale: ScaleMode::default(),
+            rotation: Rotation::default(),
+            vertex_buffer,
+            overlay_buffer,
             diffuse_texture,
+            frame: [0; FRAME_LEN],
             texture_bind_group_layout,
+            uniform_bind_group_layout,
             diffuse_bind_group,
+            palette_buffer,
+            palette_bind_group,
+            effects_buffer,
+            effects_bind_group,
+            history,
+            history_bind_groups,
+            history_index: 0,
+            preset: None,
+            chain: None,
         };
         out.reset_vertex_buffer();
-        out
+        Ok(out)
     }
 
-    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) -> Result<(), Error> {
         if new_size.width < 1 || new_size.height < 1 {
-            return;
+            return Ok(());
         }
         self.instance.poll_all(true);
         self.size = new_size;
@@ -169,16 +502,202 @@ impl<'a> Renderer<'a> {
         self.config.height = new_size.height;
         self.reset_vertex_buffer();
         self.surface.configure(&self.device, &self.config);
+        self.rebuild_chain()
     }
 
-    pub fn render(&mut self) {
-        let vertex_buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&self.vertex_buffer),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn set_keypad(&mut self, keypad: Option<KeypadPosition>) -> Result<(), Error> {
+        self.keypad = keypad;
+        self.reset_vertex_buffer();
+        self.rebuild_chain()
+    }
+
+    pub fn set_scale(&mut self, scale: ScaleMode) -> Result<(), Error> {
+        self.scale = scale;
+        self.reset_vertex_buffer();
+        self.rebuild_chain()
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) -> Result<(), Error> {
+        self.rotation = rotation;
+        self.reset_vertex_buffer();
+        self.rebuild_chain()
+    }
+
+    //Compiles the preset's passes, keeping the built-in shader if anything fails
+    pub fn set_preset(&mut self, preset: Option<Preset>) -> Result<(), Error> {
+        self.chain = None;
+        self.preset = None;
+        if let Some(preset) = preset {
+            self.chain = Some(self.build_chain(&preset)?);
+            self.preset = Some(preset);
+        }
+        Ok(())
+    }
+
+    //Textures sized to the window have to follow it around. A preset that fails to rebuild
+    //is dropped for the built-in shader and its error returned
+    fn rebuild_chain(&mut self) -> Result<(), Error> {
+        match self.preset.take() {
+            Some(preset) => {
+                let name = preset.name.clone();
+                self.set_preset(Some(preset))
+                    .with_context(|| format!("Could not rebuild {} for the new layout", name))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn build_chain(&self, preset: &Preset) -> Result<ShaderChain, Error> {
+        //Presets draw the screen upright, so on its side the area is turned back first
+        let screen = self.layout.screen;
+        let (screen_w, screen_h) = match self.rotation.sideways() {
+            true => (screen.h, screen.w),
+            false => (screen.w, screen.h),
+        };
+        let (screen_w, screen_h) = ((screen_w.round() as u32).max(1), (screen_h.round() as u32).max(1));
+        let textures: Vec<Texture> = preset
+            .textures
+            .iter()
+            .map(|texture| {
+                let (w, h) = match texture.scale {
+                    Some(scale) => (
+                        ((WIDTH as f32 * scale).round() as u32).max(1),
+                        ((HEIGHT as f32 * scale).round() as u32).max(1),
+                    ),
+                    None => (screen_w, screen_h),
+                };
+                Texture::render_target(&self.device, &texture.name, w, h, TextureFormat::Rgba8Unorm)
+            })
+            .collect();
+        let rotated = (self.rotation != Rotation::None).then(|| {
+            let texture = Texture::render_target(&self.device, "rotated_texture", screen_w, screen_h, self.config.format);
+            let bind_group =
+                Self::texture_bind_group(&self.device, &self.texture_bind_group_layout, &texture, "rotated_bind_group");
+            (texture, bind_group)
+        });
+
+        //Compile errors are caught here rather than taking the whole program down
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let passes = preset
+            .passes
+            .iter()
+            .map(|pass| {
+                let label = pass.file.display().to_string();
+                let module = self.device.create_shader_module(ShaderModuleDescriptor {
+                    label: Some(&label),
+                    source: ShaderSource::Wgsl(Cow::Borrowed(&pass.source)),
+                });
+                //Binding 0 is the sampler, the inputs follow in the order they were listed
+                let mut entries = vec![wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }];
+                entries.extend((0..pass.inputs.len()).map(|i| wgpu::BindGroupLayoutEntry {
+                    binding: i as u32 + 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                }));
+                let input_layout = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &entries,
+                    label: Some("preset_input_layout"),
+                });
+                let pipeline_layout = self.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("preset_pipeline_layout"),
+                    bind_group_layouts: &[&self.uniform_bind_group_layout, &input_layout],
+                    push_constant_ranges: &[],
+                });
+                let format = match pass.output {
+                    Some(_) => TextureFormat::Rgba8Unorm,
+                    None => self.config.format,
+                };
+                let pipeline = self.device.create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some(&label),
+                    layout: Some(&pipeline_layout),
+                    vertex: VertexState {
+                        module: &module,
+                        entry_point: "vertex",
+                        buffers: &[],
+                    },
+                    fragment: Some(FragmentState {
+                        module: &module,
+                        entry_point: "fragment",
+                        targets: &[Some(format.into())],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    multisample: MultisampleState::default(),
+                    depth_stencil: None,
+                    multiview: None,
+                });
+                ChainPass {
+                    pipeline,
+                    bind_groups: [0, 1].map(|history| self.chain_bind_group(&textures, &input_layout, &pass.inputs, history)),
+                    output: pass.output,
+                }
+            })
+            .collect();
+        match futures::executor::block_on(self.device.pop_error_scope()) {
+            Some(e) => Err(anyhow!("{}", e)),
+            None => Ok(ShaderChain {
+                textures,
+                passes,
+                rotated,
+            }),
+        }
+    }
+
+    fn chain_bind_group(&self, textures: &[Texture], layout: &BindGroupLayout, inputs: &[Input], history: usize) -> BindGroup {
+        let sampler = &self.history[0].sampler;
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Sampler(sampler),
+        }];
+        entries.extend(inputs.iter().enumerate().map(|(i, input)| {
+            let view = match input {
+                Input::Screen => &self.history[history].view,
+                Input::Texture(t) => &textures[*t].view,
+            };
+            wgpu::BindGroupEntry {
+                binding: i as u32 + 1,
+                resource: wgpu::BindingResource::TextureView(view),
+            }
+        }));
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &entries,
+            label: Some("preset_input_bind_group"),
+        })
+    }
+
+    pub fn set_palette(&mut self, palette: &Palette) {
+        let colors = palette.to_rgba(self.config.format.is_srgb());
+        self.queue
+            .write_buffer(&self.palette_buffer, 0, bytemuck::cast_slice(&colors));
+    }
+
+    pub fn set_effects(&mut self, effects: &Effects) {
+        self.queue
+            .write_buffer(&self.effects_buffer, 0, bytemuck::bytes_of(&effects.uniform()));
+    }
+
+    pub fn render(&mut self, overlay: &[Quad]) {
+        let overlay_vertices = self.overlay_vertices(overlay);
+        let overlay_size = std::mem::size_of_val(overlay_vertices.as_slice()) as wgpu::BufferAddress;
+        if overlay_size > self.overlay_buffer.size() {
+            self.overlay_buffer = Self::create_overlay_buffer(&self.device, overlay_size);
+        }
+        self.queue
+            .write_buffer(&self.overlay_buffer, 0, bytemuck::cast_slice(&overlay_vertices));
         let frame = self
             .surface
             .get_current_texture()
@@ -187,12 +706,66 @@ impl<'a> Renderer<'a> {
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor::default());
+        //Fades the previous history into the new frame at the Chip-8's resolution
+        let previous = self.history_index;
+        self.history_index = 1 - previous;
         {
-            let mut render_pass = Self::get_render_pass(&mut encoder, &view);
-            render_pass.set_pipeline(&self.pipeline);   
+            let mut render_pass = Self::get_render_pass(&mut encoder, &self.history[self.history_index].view);
+            render_pass.set_pipeline(&self.persistence_pipeline);
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.draw(0..4, 0..1);
+            render_pass.set_bind_group(1, &self.history_bind_groups[previous], &[]);
+            render_pass.set_bind_group(2, &self.effects_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        if let Some(chain) = &self.chain {
+            for pass in &chain.passes {
+                let target = match (pass.output, &chain.rotated) {
+                    (Some(output), _) => &chain.textures[output].view,
+                    (None, Some((rotated, _))) => &rotated.view,
+                    (None, None) => continue,
+                };
+                let mut render_pass = Self::get_render_pass(&mut encoder, target);
+                render_pass.set_pipeline(&pass.pipeline);
+                render_pass.set_bind_group(0, &self.palette_bind_group, &[]);
+                render_pass.set_bind_group(1, &pass.bind_groups[self.history_index], &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+        {
+            let mut render_pass = Self::get_render_pass(&mut encoder, &view);
+            let last_pass = self.chain.as_ref().and_then(|chain| Some((chain, chain.passes.last()?)));
+            match last_pass {
+                Some((chain, pass)) => match &chain.rotated {
+                    Some((_, rotated_bind_group)) => {
+                        render_pass.set_pipeline(&self.rotate_pipeline);
+                        render_pass.set_bind_group(0, rotated_bind_group, &[]);
+                        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                        render_pass.draw(0..4, 0..1);
+                    }
+                    None => {
+                        let screen = self.layout.screen;
+                        render_pass.set_viewport(screen.x, screen.y, screen.w, screen.h, 0.0, 1.0);
+                        render_pass.set_pipeline(&pass.pipeline);
+                        render_pass.set_bind_group(0, &self.palette_bind_group, &[]);
+                        render_pass.set_bind_group(1, &pass.bind_groups[self.history_index], &[]);
+                        render_pass.draw(0..3, 0..1);
+                        render_pass.set_viewport(0.0, 0.0, self.size.width as f32, self.size.height as f32, 0.0, 1.0);
+                    }
+                },
+                _ => {
+                    render_pass.set_pipeline(&self.pipeline);
+                    render_pass.set_bind_group(0, &self.history_bind_groups[self.history_index], &[]);
+                    render_pass.set_bind_group(1, &self.palette_bind_group, &[]);
+                    render_pass.set_bind_group(2, &self.effects_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                    render_pass.draw(0..4, 0..1);
+                }
+            }
+            if !overlay_vertices.is_empty() {
+                render_pass.set_pipeline(&self.overlay_pipeline);
+                render_pass.set_vertex_buffer(0, self.overlay_buffer.slice(..overlay_size));
+                render_pass.draw(0..overlay_vertices.len() as u32, 0..1);
+            }
         }
         self.queue.submit(Some(encoder.finish()));
         frame.present();
@@ -220,46 +793,94 @@ impl<'a> Renderer<'a> {
     }
 
     pub fn reset_vertex_buffer(&mut self) {
-        let PhysicalSize { width, height } = self.size;
-        let (width, height) = (width as f32, height as f32);
-        let window_aspect_ratio = width / height;
-
-        let (x1, x2, y1, y2) = if ASPECT_RATIO > window_aspect_ratio {
-            //Vertical letterboxing
-            let height = (width / ASPECT_RATIO) / height;
-            (-1.0, 1.0, -height, height)
-        } else {
-            //Horizontal letterboxing
-            let width = (height * ASPECT_RATIO) / width;
-            (-width, width, -1.0, 1.0)
-        };
+        self.layout = Layout::new(self.size, self.keypad, self.scale, self.rotation);
+        let [x1, y2, x2, y1] = self.to_ndc(self.layout.screen);
         let pos = [[x1, y1], [x1, y2], [x2, y1], [x2, y2]];
-        const TEXTURE_COORDS: [[f32;2];4] = [[0.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]];
+        //Corners of the screen as it appears in the window, bottom left, top left, bottom right, top right
+        const CORNERS: [(f32, f32); 4] = [(0.0, 1.0), (0.0, 0.0), (1.0, 1.0), (1.0, 0.0)];
 
-        self.vertex_buffer.iter_mut().enumerate().for_each(|(i, v)| {
-            v.position = pos[i];
-            v.tex_coords = TEXTURE_COORDS[i];
+        let vertices = [0, 1, 2, 3].map(|i| {
+            let (u, v) = CORNERS[i];
+            let (tu, tv) = self.rotation.texel(u, v);
+            Vertex {
+                position: pos[i],
+                tex_coords: [tu, tv],
+            }
         });
+        self.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
     }
 
+    //Rounded up to a power of two so a growing overlay does not reallocate every frame
+    fn create_overlay_buffer(device: &Device, size: wgpu::BufferAddress) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overlay Vertex Buffer"),
+            size: size.next_power_of_two().max(4096),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    //Window pixels to normalized device coordinates as [left, top, right, bottom]
+    fn to_ndc(&self, rect: Rect) -> [f32; 4] {
+        let (w, h) = (self.size.width as f32, self.size.height as f32);
+        [
+            rect.x / w * 2.0 - 1.0,
+            1.0 - rect.y / h * 2.0,
+            (rect.x + rect.w) / w * 2.0 - 1.0,
+            1.0 - (rect.y + rect.h) / h * 2.0,
+        ]
+    }
+
+    fn overlay_vertices(&self, quads: &[Quad]) -> Vec<OverlayVertex> {
+        quads
+            .iter()
+            .flat_map(|quad| {
+                let [x1, y1, x2, y2] = self.to_ndc(quad.rect);
+                [[x1, y1], [x1, y2], [x2, y1], [x2, y1], [x1, y2], [x2, y2]].map(|position| {
+                    OverlayVertex {
+                        position,
+                        color: quad.color,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    //Uploads the band of rows that differ from the last frame, if any
     pub fn update_screen(&mut self, bytes: &[u8]) {
-        self.diffuse_texture = Texture::from_bytes(&self.device, &self.queue, bytes).unwrap();
-        self.diffuse_bind_group = self.device.create_bind_group(
+        const ROW_BYTES: usize = WIDTH / 8;
+        let changed = |&y: &usize| bytes[y * ROW_BYTES..][..ROW_BYTES] != self.frame[y * ROW_BYTES..][..ROW_BYTES];
+        let Some(first) = (0..HEIGHT).find(changed) else {
+            return;
+        };
+        let last = (0..HEIGHT).rev().find(changed).unwrap_or(first);
+        let band = &bytes[first * ROW_BYTES..(last + 1) * ROW_BYTES];
+        let pixels: Vec<u8> = band
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| if byte & (0x80 >> bit) != 0 { 0xFF } else { 0x00 }))
+            .collect();
+        self.diffuse_texture.write_rows(&self.queue, &pixels, first as u32);
+        self.frame.copy_from_slice(bytes);
+    }
+
+    fn texture_bind_group(device: &Device, layout: &BindGroupLayout, texture: &Texture, label: &str) -> BindGroup {
+        device.create_bind_group(
             &wgpu::BindGroupDescriptor {
-                layout: &self.texture_bind_group_layout,
+                layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&self.diffuse_texture.view),
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.diffuse_texture.sampler),
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
                     }
                 ],
-                label: Some("diffuse_bind_group"),
+                label: Some(label),
             }
-        );
+        )
     }
 }
 
@@ -281,3 +902,22 @@ impl Vertex {
         }
     }
 }
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct OverlayVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl OverlayVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
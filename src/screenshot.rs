@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error};
+use image::{GrayImage, ImageFormat, Luma, Rgb, RgbImage};
+
+use crate::{palette::Palette, HEIGHT, WIDTH};
+
+fn is_lit(buffer: &[u8], x: usize, y: usize) -> bool {
+    let i = y * WIDTH + x;
+    buffer[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+//The screen exactly as the Chip-8 sees it, lit pixels white
+pub fn save_raw(buffer: &[u8], path: &Path) -> Result<(), Error> {
+    let image = GrayImage::from_fn(WIDTH as u32, HEIGHT as u32, |x, y| {
+        Luma([if is_lit(buffer, x as usize, y as usize) { 0xFF } else { 0x00 }])
+    });
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .with_context(|| format!("Could not save screenshot {}", path.display()))
+}
+
+//Binary PBM, which packs pixels eight to a byte from the left just like the screen buffer
+pub fn save_pbm(buffer: &[u8], path: &Path) -> Result<(), Error> {
+    let mut bytes = format!("P4\n{} {}\n", WIDTH, HEIGHT).into_bytes();
+    bytes.extend_from_slice(buffer);
+    fs::write(path, bytes).with_context(|| format!("Could not save screenshot {}", path.display()))
+}
+
+//A line of text per row, # for lit pixels and . for the rest
+pub fn ascii(buffer: &[u8]) -> String {
+    (0..HEIGHT)
+        .map(|y| {
+            let row: String = (0..WIDTH).map(|x| if is_lit(buffer, x, y) { '#' } else { '.' }).collect();
+            row + "\n"
+        })
+        .collect()
+}
+
+//Every pixel blown up to a scale x scale block in the palette's colors
+pub fn save_scaled(buffer: &[u8], path: &Path, palette: &Palette, scale: u32) -> Result<(), Error> {
+    let scale = scale.max(1);
+    let [background, foreground, ..] = palette.colors;
+    let image = RgbImage::from_fn(WIDTH as u32 * scale, HEIGHT as u32 * scale, |x, y| {
+        match is_lit(buffer, (x / scale) as usize, (y / scale) as usize) {
+            true => Rgb(foreground),
+            false => Rgb(background),
+        }
+    });
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .with_context(|| format!("Could not save screenshot {}", path.display()))
+}
+
+//<rom>-<timestamp><ending> in the given directory, the ending includes the extension
+pub fn file_path(dir: &Path, rom_path: &Path, timestamp: &str, ending: &str) -> PathBuf {
+    let rom = rom_path.file_stem().and_then(|s| s.to_str()).unwrap_or("chip8");
+    dir.join(format!("{}-{}{}", rom, timestamp, ending))
+}
+
+//YYYYMMDD-HHMMSS-mmm in UTC, to the millisecond so two saves in a row get their own files
+pub fn timestamp(time: SystemTime) -> String {
+    let millis = time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let (secs, millis) = (millis / 1000, millis % 1000);
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        millis
+    )
+}
+
+//Days since 1970-01-01 to a Gregorian date, from Howard Hinnant's date algorithms
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn dates_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        //Leap days of a year divisible by 400 and the first day after one
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        //2100 is not a leap year
+        assert_eq!(civil_from_days(47540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47541), (2100, 3, 1));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000-000");
+        let time = UNIX_EPOCH + Duration::from_millis(1_234_567_890_123);
+        assert_eq!(timestamp(time), "20090213-233130-123");
+        //Saves within the same second still differ
+        assert_ne!(timestamp(time), timestamp(time + Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn file_names() {
+        let path = file_path(Path::new("shots"), Path::new("roms/pong.ch8"), "19700101-000000-000", "-x8.png");
+        assert_eq!(path, Path::new("shots/pong-19700101-000000-000-x8.png"));
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    f32::consts::PI,
+    num::NonZeroU32,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Error};
+use softbuffer::{Context, Surface};
+use winit::{dpi::PhysicalSize, window::Window};
+
+use crate::{
+    effects::Effects,
+    layout::{KeypadPosition, Layout, Quad, Rotation, ScaleMode},
+    palette::Palette,
+    HEIGHT, WIDTH,
+};
+
+//There is no vsync to pace the emulator, so frames are spaced out by hand
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+//Scales and colors the screen on the CPU for machines without a usable GPU.
+//Phosphor persistence and scanlines work, curvature, bloom and shader presets do not
+pub struct SoftwareRenderer<'a> {
+    surface: Surface<&'a Window, &'a Window>,
+    size: PhysicalSize<u32>,
+    layout: Layout,
+    keypad: Option<KeypadPosition>,
+    scale: ScaleMode,
+    rotation: Rotation,
+    palette: Palette,
+    effects: Effects,
+    lit: Vec<bool>,
+    brightness: Vec<f32>,
+    last_present: Instant,
+}
+
+impl<'a> SoftwareRenderer<'a> {
+    pub fn new(window: &'a Window) -> Result<Self, Error> {
+        let context = Context::new(window).map_err(|e| anyhow!("Could not create software context: {}", e))?;
+        let surface =
+            Surface::new(&context, window).map_err(|e| anyhow!("Could not create software surface: {}", e))?;
+        let mut size = window.inner_size();
+        size.width = size.width.max(1);
+        size.height = size.height.max(1);
+        let mut out = Self {
+            surface,
+            size,
+            layout: Layout::default(),
+            keypad: None,
+            scale: ScaleMode::default(),
+            rotation: Rotation::default(),
+            palette: Palette::default(),
+            effects: Effects::default(),
+            lit: vec![false; WIDTH * HEIGHT],
+            brightness: vec![0.0; WIDTH * HEIGHT],
+            last_present: Instant::now(),
+        };
+        out.resize(size);
+        Ok(out)
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        let (Some(width), Some(height)) = (NonZeroU32::new(new_size.width), NonZeroU32::new(new_size.height)) else {
+            return;
+        };
+        if let Err(e) = self.surface.resize(width, height) {
+            eprintln!("Could not resize software surface: {}", e);
+            return;
+        }
+        self.size = new_size;
+        self.layout = Layout::new(self.size, self.keypad, self.scale, self.rotation);
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn set_keypad(&mut self, keypad: Option<KeypadPosition>) {
+        self.keypad = keypad;
+        self.layout = Layout::new(self.size, self.keypad, self.scale, self.rotation);
+    }
+
+    pub fn set_scale(&mut self, scale: ScaleMode) {
+        self.scale = scale;
+        self.layout = Layout::new(self.size, self.keypad, self.scale, self.rotation);
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+        self.layout = Layout::new(self.size, self.keypad, self.scale, self.rotation);
+    }
+
+    pub fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.clone();
+    }
+
+    pub fn set_effects(&mut self, effects: &Effects) {
+        self.effects = *effects;
+    }
+
+    pub fn update_screen(&mut self, bytes: &[u8]) {
+        for (i, lit) in self.lit.iter_mut().enumerate() {
+            *lit = bytes[i / 8] & (0x80 >> (i % 8)) != 0;
+        }
+    }
+
+    pub fn render(&mut self, overlay: &[Quad]) {
+        let effects = self.effects.uniform();
+        for (brightness, &lit) in self.brightness.iter_mut().zip(&self.lit) {
+            *brightness = match lit {
+                true => 1.0,
+                false => *brightness * effects.decay,
+            };
+        }
+
+        let mut buffer = match self.surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                eprintln!("Could not get software buffer: {}", e);
+                return;
+            }
+        };
+        let (w, h) = (self.size.width as usize, self.size.height as usize);
+        buffer.fill(0);
+
+        //Nearest neighbour scaling of the Chip-8 screen into its rect
+        let [background, foreground] = [0, 1].map(|i| self.palette.colors[i].map(|c| c as f32));
+        let screen = self.layout.screen;
+        let (x0, y0) = (screen.x.round() as usize, screen.y.round() as usize);
+        let x1 = ((screen.x + screen.w).round() as usize).min(w);
+        let y1 = ((screen.y + screen.h).round() as usize).min(h);
+        //Sampled at pixel centers so a flipped screen lines up the same way
+        let center = |i: usize, start: usize, end: usize| ((i - start) as f32 + 0.5) / (end - start) as f32;
+        for y in y0..y1 {
+            let v = center(y, y0, y1);
+            let row = &mut buffer[y * w..(y + 1) * w];
+            for (x, pixel) in (x0..x1).zip(&mut row[x0..x1]) {
+                let (tu, tv) = self.rotation.texel(center(x, x0, x1), v);
+                let (tx, ty) = (tu * WIDTH as f32, tv * HEIGHT as f32);
+                let (cx, cy) = ((tx as usize).min(WIDTH - 1), (ty as usize).min(HEIGHT - 1));
+                let brightness = self.brightness[cy * WIDTH + cx];
+                //Darkens the gaps between the rows of Chip-8 pixels
+                let scanline = match effects.scanlines > 0.0 {
+                    true => 1.0 - effects.scanlines * (1.0 - (ty.fract() * PI).sin()),
+                    false => 1.0,
+                };
+                let color = [0, 1, 2].map(|c| {
+                    (background[c] + (foreground[c] - background[c]) * brightness) * scanline
+                });
+                *pixel = pack(color);
+            }
+        }
+
+        for quad in overlay {
+            let [r, g, b, a] = quad.color;
+            let color = [r, g, b].map(|c| linear_to_srgb(c) * 255.0);
+            let qx0 = (quad.rect.x.round().max(0.0) as usize).min(w);
+            let qy0 = (quad.rect.y.round().max(0.0) as usize).min(h);
+            let qx1 = ((quad.rect.x + quad.rect.w).round().max(0.0) as usize).min(w);
+            let qy1 = ((quad.rect.y + quad.rect.h).round().max(0.0) as usize).min(h);
+            for y in qy0..qy1 {
+                for pixel in &mut buffer[y * w + qx0..y * w + qx1] {
+                    let under = unpack(*pixel);
+                    *pixel = pack([0, 1, 2].map(|c| under[c] * (1.0 - a) + color[c] * a));
+                }
+            }
+        }
+
+        if let Some(wait) = FRAME_TIME.checked_sub(self.last_present.elapsed()) {
+            thread::sleep(wait);
+        }
+        self.last_present = Instant::now();
+        if let Err(e) = buffer.present() {
+            eprintln!("Could not present software buffer: {}", e);
+        }
+    }
+}
+
+//softbuffer pixels are 0RGB
+fn pack([r, g, b]: [f32; 3]) -> u32 {
+    let channel = |c: f32| c.clamp(0.0, 255.0) as u32;
+    channel(r) << 16 | channel(g) << 8 | channel(b)
+}
+
+fn unpack(pixel: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((pixel >> shift) & 0xFF) as f32)
+}
+
+//Overlay colors are linear, as the GPU renderer's sRGB surface expects
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
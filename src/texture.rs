@@ -1,5 +1,3 @@
-use anyhow::Error;
-use image::{DynamicImage, GenericImageView, ImageFormat};
 use wgpu::{
     AddressMode, Device, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d, Queue,
     Sampler, SamplerDescriptor, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
@@ -15,51 +13,74 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn from_bytes(device: &Device, queue: &Queue, bytes: &[u8]) -> Result<Self, Error> {
-        let header_string = format!("P4 {} {}\n", WIDTH, HEIGHT);
-        let header: &[u8] = header_string.as_bytes();
-        //Mapped NOT due to the specifications of pbm format
-        let file = [header, bytes].concat();
-        let img = image::load_from_memory_with_format(&file, ImageFormat::Pnm)?;
-        Self::from_image(device, queue, &img)
-    }
-
-    fn from_image(device: &Device, queue: &Queue, img: &DynamicImage) -> Result<Self, Error> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
-
-        let size = Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
-        };
+    //One byte per Chip-8 pixel, filled in with write_rows as the screen changes
+    pub fn screen(device: &Device) -> Self {
         let texture = device.create_texture(&TextureDescriptor {
-            label: None,
-            size,
+            label: Some("screen_texture"),
+            size: Extent3d {
+                width: WIDTH as u32,
+                height: HEIGHT as u32,
+                depth_or_array_layers: 1,
+            },
             mip_level_count: 1,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8UnormSrgb,
+            format: TextureFormat::R8Unorm,
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
             view_formats: &[],
         });
+        Self::from_texture(device, texture)
+    }
 
+    //Uploads whole rows of pixels starting at the given row
+    pub fn write_rows(&self, queue: &Queue, pixels: &[u8], first_row: u32) {
+        let width = self.texture.width();
+        let rows = pixels.len() as u32 / width;
         queue.write_texture(
             ImageCopyTexture {
                 aspect: TextureAspect::All,
-                texture: &texture,
+                texture: &self.texture,
                 mip_level: 0,
-                origin: Origin3d::ZERO,
+                origin: Origin3d {
+                    x: 0,
+                    y: first_row,
+                    z: 0,
+                },
             },
-            &rgba,
+            pixels,
             ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(width),
+                rows_per_image: Some(rows),
+            },
+            Extent3d {
+                width,
+                height: rows,
+                depth_or_array_layers: 1,
             },
-            size,
         );
+    }
+
+    //Offscreen texture that passes can draw into
+    pub fn render_target(device: &Device, label: &str, width: u32, height: u32, format: TextureFormat) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Self::from_texture(device, texture)
+    }
 
+    fn from_texture(device: &Device, texture: wgpu::Texture) -> Self {
         let view = texture.create_view(&TextureViewDescriptor::default());
         let sampler = device.create_sampler(&SamplerDescriptor {
             address_mode_u: AddressMode::ClampToEdge,
@@ -71,10 +92,10 @@ impl Texture {
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
     }
 }
@@ -0,0 +1,107 @@
+//Small roms that keep one class of instruction busy, for measuring how fast the core runs.
+//Shared by the bench subcommand and the criterion benches
+use crate::chip8::{
+    quirks::{Platform, Quirks},
+    Chip8,
+};
+
+//Groups of opcodes that cost about the same to run
+pub const CLASSES: [&str; 9] = ["load", "alu", "skip", "flow", "random", "draw", "clear", "memory", "other"];
+
+pub fn class(op: u16) -> &'static str {
+    match op & 0xF000 {
+        0x0000 => match op {
+            0x00E0 => "clear",
+            0x00EE => "flow",
+            _ => "other",
+        },
+        0x1000 | 0x2000 | 0xB000 => "flow",
+        0x3000 | 0x4000 | 0x5000 | 0x9000 | 0xE000 => "skip",
+        0x6000 | 0x7000 | 0xA000 => "load",
+        0x8000 => match op & 0xF {
+            0x0 => "load",
+            _ => "alu",
+        },
+        0xC000 => "random",
+        0xD000 => "draw",
+        _ => match op & 0xFF {
+            0x33 | 0x55 | 0x65 => "memory",
+            0x0A => "other",
+            _ => "load",
+        },
+    }
+}
+
+pub struct Workload {
+    pub class: &'static str,
+    pub rom: Vec<u8>,
+    pub quirks: Quirks,
+}
+
+impl Workload {
+    pub fn cpu(&self) -> Chip8 {
+        Chip8::new().with_quirks(self.quirks).with_rom(&self.rom).with_seed(0)
+    }
+}
+
+//Words of the loop body, which is repeated to fill the loop
+const LOOP_LEN: usize = 64;
+
+//Setup runs once, then the body repeats until a jump back to its start. None of the bodies
+//skip, so the jump is the only instruction outside the class
+fn workload(class: &'static str, setup: &[u16], body: &[u16], quirks: Quirks) -> Workload {
+    let start = 0x200 + 2 * setup.len() as u16;
+    let mut words = setup.to_vec();
+    words.extend(body.iter().cycle().take(LOOP_LEN));
+    words.push(0x1000 | start);
+    Workload {
+        class,
+        rom: words.iter().flat_map(|word| word.to_be_bytes()).collect(),
+        quirks,
+    }
+}
+
+pub fn workloads() -> Vec<Workload> {
+    let chip8 = Platform::Chip8.quirks();
+    //Fx55 and Fx65 would walk I off the end of RAM without this
+    let leave_i = Platform::SuperChip.quirks();
+    //Calls to a return placed just past the loop, every fourth word a jump to the next one
+    let ret = 0x200 + 2 * (LOOP_LEN as u16 + 1);
+    let calls: Vec<u16> = (0..LOOP_LEN as u16)
+        .map(|i| match i % 4 {
+            3 => 0x1000 | (0x200 + 2 * (i + 1)),
+            _ => 0x2000 | ret,
+        })
+        .collect();
+    let mut flow = workload("flow", &[], &calls, chip8);
+    flow.rom.extend(0x00EE_u16.to_be_bytes());
+    vec![
+        workload("load", &[], &[0x6A05, 0x7A01, 0x8AB0, 0xA300, 0xFA07, 0xFA15, 0xFA1E, 0xFA29], chip8),
+        workload("alu", &[], &[0x8AB4, 0x8AB5, 0x8AB1, 0x8AB6, 0x8ABE, 0x8AB7, 0x8AB2, 0x8AB3], chip8),
+        //VA and VB stay 0 and no key is held, so none of these skip
+        workload("skip", &[], &[0x3AFF, 0x4A00, 0x9AB0, 0xEA9E], chip8),
+        flow,
+        workload("random", &[], &[0xCAFF], chip8),
+        //The font's 0, drawn at a few places so sprites cross byte boundaries
+        workload("draw", &[0xA000, 0x6A03, 0x6B0A], &[0xD015, 0xDAB5, 0xD0B5, 0xDA05], chip8),
+        workload("clear", &[], &[0x00E0], chip8),
+        workload("memory", &[0xA300], &[0xFF55, 0xFF65, 0xFA33], leave_i),
+    ]
+}
+
+//Runs instructions straight through Chip8::update with no frame pacing or vblank wait,
+//ticking the timers every ipf instructions so roms waiting on DT still get somewhere.
+//Returns how many ran, fewer than asked for when the rom stopped to wait for a key or on an
+//error, since nothing presses keys here and the error screen is not the rom
+pub fn run(cpu: &mut Chip8, instructions: u64, ipf: u32) -> u64 {
+    for n in 1..=instructions {
+        if cpu.halted() || cpu.error().is_some() {
+            return n - 1;
+        }
+        cpu.update();
+        if n % ipf as u64 == 0 {
+            cpu.update_timers();
+        }
+    }
+    instructions
+}
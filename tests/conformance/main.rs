@@ -0,0 +1,255 @@
+//Runs test roms headlessly under the quirks of every platform and compares the final
+//screen with the golden PBMs in tests/golden. `BLESS=1 cargo test` writes the goldens
+//from the current behaviour instead, check the new images before committing them
+
+mod roms;
+
+use std::{
+    env, fs,
+    ops::Range,
+    path::Path,
+    process::{self, Command, Stdio},
+};
+
+use chip8_emulator::{
+    chip8::{quirks::Platform, Chip8},
+    HEIGHT, WIDTH,
+};
+
+const PLATFORMS: [(Platform, &str); 3] = [
+    (Platform::Chip8, "chip8"),
+    (Platform::SuperChip, "schip"),
+    (Platform::XoChip, "xochip"),
+];
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+//Roms from the community test suite, https://github.com/Timendus/chip8-test-suite,
+//are not bundled yet. Their tests are ignored, run them with --ignored after tests/fetch-suite.sh
+const SUITE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms");
+
+//Chip-8 key going down or up at the start of a frame
+type Key = (u64, u8, bool);
+
+struct Run {
+    screen: Vec<u8>,
+    //Frames that ended with the buzzer on
+    sound: Vec<u64>,
+}
+
+fn run(rom: &[u8], platform: Platform, frames: u64, keys: &[Key]) -> Run {
+    let quirks = platform.quirks();
+    let mut cpu = Chip8::new().with_quirks(quirks).with_rom(rom);
+    let mut sound = Vec::new();
+    for frame in 0..frames {
+        for &(_, key, pressed) in keys.iter().filter(|(at, ..)| *at == frame) {
+            cpu.set_key(key, pressed);
+        }
+        cpu.run_frame(platform.tick_rate(), quirks.vblank);
+        if cpu.sound_active() {
+            sound.push(frame);
+        }
+    }
+    let screen = cpu.get_display_buffer().borrow().to_vec();
+    Run { screen, sound }
+}
+
+fn pbm(screen: &[u8]) -> Vec<u8> {
+    let mut bytes = format!("P4\n{} {}\n", WIDTH, HEIGHT).into_bytes();
+    bytes.extend_from_slice(screen);
+    bytes
+}
+
+fn lit(screen: &[u8], x: usize, y: usize) -> bool {
+    let i = y * WIDTH + x;
+    screen[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+//# lit in both, . dark in both, + lit only in the actual screen, - lit only in the golden one
+fn diff(actual: &[u8], golden: &[u8]) -> String {
+    let mut out = String::new();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            out.push(match (lit(actual, x, y), lit(golden, x, y)) {
+                (true, true) => '#',
+                (false, false) => '.',
+                (true, false) => '+',
+                (false, true) => '-',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+//Runs the rom on every platform, gathering all mismatches before failing
+fn check(name: &str, rom: &[u8], frames: u64, keys: impl Fn(Platform) -> Vec<Key>) {
+    let bless = env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    for (platform, id) in PLATFORMS {
+        let run = run(rom, platform, frames, &keys(platform));
+        let path = Path::new(GOLDEN_DIR).join(format!("{}.{}.pbm", name, id));
+        let actual = pbm(&run.screen);
+        if bless {
+            fs::create_dir_all(GOLDEN_DIR).unwrap();
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        match fs::read(&path) {
+            Ok(golden) if golden == actual => (),
+            Ok(golden) if golden.len() == actual.len() => {
+                let screen = &golden[golden.len() - run.screen.len()..];
+                failures.push(format!("{} differs from {}:\n{}", id, path.display(), diff(&run.screen, screen)));
+            }
+            Ok(_) => failures.push(format!("{} is not a {}x{} PBM", path.display(), WIDTH, HEIGHT)),
+            Err(_) => failures.push(format!("{} is missing, run with BLESS=1 to create it", path.display())),
+        }
+    }
+    assert!(failures.is_empty(), "{}\n{}", name, failures.join("\n"));
+}
+
+//The suite tests are ignored by default and fail when run without their rom
+fn check_suite(file: &str, frames: u64, keys: impl Fn(Platform) -> Vec<Key>) {
+    let path = Path::new(SUITE_DIR).join(file);
+    let rom = fs::read(&path).unwrap_or_else(|e| panic!("Could not read {}: {}", path.display(), e));
+    check(&format!("suite-{}", file.trim_end_matches(".ch8")), &rom, frames, keys);
+}
+
+fn bytes(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+fn no_keys(_: Platform) -> Vec<Key> {
+    vec![]
+}
+
+#[test]
+fn logo() {
+    check("logo", &bytes(roms::LOGO), 30, no_keys);
+}
+
+#[test]
+fn opcodes() {
+    check("opcodes", &bytes(roms::OPCODES), 60, no_keys);
+}
+
+#[test]
+fn flags() {
+    check("flags", &bytes(roms::FLAGS), 120, no_keys);
+}
+
+#[test]
+fn quirks() {
+    check("quirks", &bytes(roms::QUIRKS), 120, no_keys);
+}
+
+#[test]
+fn keypad() {
+    //Fx0A takes a key when it is let go, so 5 is held until the end for the last check
+    let keys = [(10, 0x1, true), (12, 0x1, false), (20, 0xA, true), (22, 0xA, false), (30, 0x5, true)];
+    check("keypad", &bytes(roms::KEYPAD), 40, |_| keys.to_vec());
+}
+
+#[test]
+fn beep() {
+    let rom = bytes(roms::BEEP);
+    check("beep", &rom, 60, no_keys);
+    //Half a second, the timer is set in the first frame and runs out at the end of the 30th
+    let expected: Range<u64> = 0..29;
+    for (platform, id) in PLATFORMS {
+        let run = run(&rom, platform, 60, &[]);
+        assert_eq!(run.sound, expected.clone().collect::<Vec<_>>(), "buzzer on {}", id);
+    }
+}
+
+//Runs the emulator binary headless with --wav and returns the file it wrote
+fn record_wav(rom: &[u8], frames: u64, name: &str) -> Vec<u8> {
+    let dir = env::temp_dir().join(format!("chip8-wav-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (rom_path, wav_path) = (dir.join(format!("{}.ch8", name)), dir.join(format!("{}.wav", name)));
+    fs::write(&rom_path, rom).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_chip8-emulator"))
+        .current_dir(&dir)
+        .args(["run", "--headless", "--frames", &frames.to_string(), "--wav"])
+        .args([&wav_path, &rom_path])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let wav = fs::read(&wav_path).unwrap();
+    fs::remove_file(&rom_path).unwrap();
+    fs::remove_file(&wav_path).unwrap();
+    wav
+}
+
+#[test]
+fn beep_wav_is_repeatable() {
+    let rom = bytes(roms::BEEP);
+    let wav = record_wav(&rom, 60, "beep-1");
+    assert!(wav == record_wav(&rom, 60, "beep-2"), "two recordings of the same frames differ");
+
+    let samples: Vec<i16> = hound::WavReader::new(wav.as_slice())
+        .unwrap()
+        .into_samples()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    //735 samples a frame at 44.1 kHz, and the tone fills exactly the 30 frames of the sound timer
+    let (frame, beep) = (44100 / 60, 30 * 44100 / 60);
+    assert_eq!(samples.len(), 60 * frame);
+    assert_ne!(samples[0], 0);
+    //Full volume once the attack is over, until the release starts right where the timer ran out
+    let full = samples[200].unsigned_abs();
+    assert!(samples[200..beep].iter().all(|s| s.unsigned_abs() == full));
+    assert!(samples[beep].unsigned_abs() < full);
+    assert!(samples[beep + frame..].iter().all(|&s| s == 0));
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_chip8_logo() {
+    check_suite("1-chip8-logo.ch8", 60, no_keys);
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_ibm_logo() {
+    check_suite("2-ibm-logo.ch8", 60, no_keys);
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_corax() {
+    check_suite("3-corax+.ch8", 60, no_keys);
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_flags() {
+    check_suite("4-flags.ch8", 120, no_keys);
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_quirks() {
+    //The rom asks which platform to test, 1, 2 or 3 in platform order
+    check_suite("5-quirks.ch8", 600, |platform| {
+        let key = match platform {
+            Platform::Chip8 => 0x1,
+            Platform::SuperChip => 0x2,
+            Platform::XoChip => 0x3,
+        };
+        vec![(30, key, true), (32, key, false)]
+    });
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_keypad() {
+    //Picks the Fx0A check from the menu and answers it with key 5
+    check_suite("6-keypad.ch8", 120, |_| vec![(30, 0x3, true), (32, 0x3, false), (60, 0x5, true), (62, 0x5, false)]);
+}
+
+#[test]
+#[ignore = "needs the suite roms from tests/fetch-suite.sh"]
+fn suite_beep() {
+    //The rom beeps while B is held
+    check_suite("7-beep.ch8", 60, |_| vec![(10, 0xB, true)]);
+}
@@ -0,0 +1,451 @@
+//Test roms assembled by hand, one instruction per word with data packed two bytes
+//to a word. Comments give the assembly and the address of each label
+
+//Spells CHIP8 in big custom sprites and underlines it, using only clear, the
+//register and index loads, add and sprite, like the IBM logo test
+pub const LOGO: &[u16] = &[
+    0x00E0, //clear
+    0xA228, //i := letters
+    0x6004, //v0 := 0x04
+    0x6108, //v1 := 0x08
+    0x620A, //v2 := 0x0A
+    0x6305, //v3 := 0x05
+    //letter, 0x20C
+    0xD01A, //sprite v0 v1 10
+    0x700C, //v0 += 0x0C
+    0xF21E, //i += v2
+    0x73FF, //v3 += 0xFF
+    0x3300, //skip if v3 == 0x00
+    0x120C, //jump letter
+    0xA25A, //i := line
+    0x6000, //v0 := 0x00
+    0x6115, //v1 := 0x15
+    //underline, 0x21E
+    0xD011, //sprite v0 v1 1
+    0x7008, //v0 += 0x08
+    0x3040, //skip if v0 == 0x40
+    0x121E, //jump underline
+    //halt, 0x226
+    0x1226, //jump halt
+    //letters, 0x228
+    0x3C7E, 0xE6C0, 0xC0C0, 0xC0E6, 0x7E3C, //C
+    0xC6C6, 0xC6C6, 0xFEFE, 0xC6C6, 0xC6C6, //H
+    0x7E7E, 0x1818, 0x1818, 0x1818, 0x7E7E, //I
+    0xFCFE, 0xC6C6, 0xFEFC, 0xC0C0, 0xC0C0, //P
+    0x7CFE, 0xC6C6, 0x7C7C, 0xC6C6, 0xFE7C, //8
+    //line, 0x25A
+    0xFF00, //line
+];
+
+//Checks one instruction at a time, drawing the number of every check that passes:
+//1-9 and A-C on the first row, D-F on the second. A missing digit is a failing check
+pub const OPCODES: &[u16] = &[
+    0x00E0, //clear
+    0x6A02, //vA := 0x02
+    0x6B02, //vB := 0x02
+    0x6405, //v4 := 0x05
+    0x3405, //skip if v4 == 0x05
+    0x1210, //jump t2
+    0x6001, //v0 := 0x01
+    0x22D8, //call mark
+    //t2, 0x210
+    0x4406, //skip if v4 != 0x06
+    0x1218, //jump t3
+    0x6002, //v0 := 0x02
+    0x22D8, //call mark
+    //t3, 0x218
+    0x6505, //v5 := 0x05
+    0x5450, //skip if v4 == v5
+    0x1222, //jump t4
+    0x6003, //v0 := 0x03
+    0x22D8, //call mark
+    //t4, 0x222
+    0x6506, //v5 := 0x06
+    0x9450, //skip if v4 != v5
+    0x122C, //jump t5
+    0x6004, //v0 := 0x04
+    0x22D8, //call mark
+    //t5, 0x22C
+    0x6600, //v6 := 0x00
+    0x22E8, //call sub
+    0x3642, //skip if v6 == 0x42
+    0x1238, //jump t6
+    0x6005, //v0 := 0x05
+    0x22D8, //call mark
+    //t6, 0x238
+    0xA2F2, //i := buf
+    0x6481, //v4 := 0x81
+    0xF433, //bcd v4
+    0xA2F2, //i := buf
+    0xF265, //load v0 - v2
+    0x4102, //skip if v1 != 0x02
+    0x3209, //skip if v2 == 0x09
+    0x124C, //jump t7
+    0x6006, //v0 := 0x06
+    0x22D8, //call mark
+    //t7, 0x24C
+    0x6011, //v0 := 0x11
+    0x6122, //v1 := 0x22
+    0x6233, //v2 := 0x33
+    0x6344, //v3 := 0x44
+    0xA2F2, //i := buf
+    0xF355, //save v0 - v3
+    0x6000, //v0 := 0x00
+    0x6100, //v1 := 0x00
+    0x6200, //v2 := 0x00
+    0x6300, //v3 := 0x00
+    0xA2F2, //i := buf
+    0xF365, //load v0 - v3
+    0x4011, //skip if v0 != 0x11
+    0x3344, //skip if v3 == 0x44
+    0x126E, //jump t8
+    0x6007, //v0 := 0x07
+    0x22D8, //call mark
+    //t8, 0x26E
+    0xA2EE, //i := table
+    0x6402, //v4 := 0x02
+    0xF41E, //i += v4
+    0xF065, //load v0 - v0
+    0x3077, //skip if v0 == 0x77
+    0x127E, //jump t9
+    0x6008, //v0 := 0x08
+    0x22D8, //call mark
+    //t9, 0x27E
+    0x6420, //v4 := 0x20
+    0xF415, //delay := v4
+    0xF507, //v5 := delay
+    0x3520, //skip if v5 == 0x20
+    0x128C, //jump tA
+    0x6009, //v0 := 0x09
+    0x22D8, //call mark
+    //tA, 0x28C
+    0x6455, //v4 := 0x55
+    0x8540, //v5 := v4
+    0x3555, //skip if v5 == 0x55
+    0x1298, //jump tB
+    0x600A, //v0 := 0x0A
+    0x22D8, //call mark
+    //tB, 0x298
+    0x6401, //v4 := 0x01
+    0xC400, //v4 := random 0x00
+    0x3400, //skip if v4 == 0x00
+    0x12A4, //jump tC
+    0x600B, //v0 := 0x0B
+    0x22D8, //call mark
+    //tC, 0x2A4
+    0x6405, //v4 := 0x05
+    0xE4A1, //skip if key v4 is not pressed
+    0x12AE, //jump tD
+    0x600C, //v0 := 0x0C
+    0x22D8, //call mark
+    //tD, 0x2AE
+    0xA2EC, //i := dot
+    0x683C, //v8 := 0x3C
+    0x691C, //v9 := 0x1C
+    0xD891, //sprite v8 v9 1
+    0x3F00, //skip if vF == 0x00
+    0x12BE, //jump tE
+    0x600D, //v0 := 0x0D
+    0x22D8, //call mark
+    //tE, 0x2BE
+    0xA2EC, //i := dot
+    0xD891, //sprite v8 v9 1
+    0x3F01, //skip if vF == 0x01
+    0x12CA, //jump tF
+    0x600E, //v0 := 0x0E
+    0x22D8, //call mark
+    //tF, 0x2CA
+    0x64FF, //v4 := 0xFF
+    0x7402, //v4 += 0x02
+    0x3401, //skip if v4 == 0x01
+    0x12D6, //jump done
+    0x600F, //v0 := 0x0F
+    0x22D8, //call mark
+    //done, 0x2D6
+    //halt, 0x2D6
+    0x12D6, //jump halt
+    //mark, 0x2D8
+    0xF029, //i := hex v0
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0x3A3E, //skip if vA == 0x3E
+    0x00EE, //return
+    0x6A02, //vA := 0x02
+    0x7B06, //vB += 0x06
+    0x00EE, //return
+    //sub, 0x2E8
+    0x6642, //v6 := 0x42
+    0x00EE, //return
+    //dot, 0x2EC
+    0x8000, //dot
+    //table, 0x2EE
+    0x0000, 0x7700, //table
+    //buf, 0x2F2
+    0x0000, 0x0000, //buf
+];
+
+//Shows the result and VF of the arithmetic and logic instructions, three to a
+//row: two digits of the result and one of VF. In order: add, add with carry,
+//subtract, subtract with borrow, reverse subtract with and without borrow, shift
+//right, shift left, or, and, xor, VF as the target of an add, VF as the source of
+//an add with carry, and 7xnn leaving VF alone
+pub const FLAGS: &[u16] = &[
+    0x00E0, //clear
+    0x6A00, //vA := 0x00
+    0x6B00, //vB := 0x00
+    0x6410, //v4 := 0x10
+    0x6520, //v5 := 0x20
+    0x8454, //v4 += v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x64F0, //v4 := 0xF0
+    0x6520, //v5 := 0x20
+    0x8454, //v4 += v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6430, //v4 := 0x30
+    0x6510, //v5 := 0x10
+    0x8455, //v4 -= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6410, //v4 := 0x10
+    0x6530, //v5 := 0x30
+    0x8455, //v4 -= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6410, //v4 := 0x10
+    0x6530, //v5 := 0x30
+    0x8457, //v4 =- v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6430, //v4 := 0x30
+    0x6510, //v5 := 0x10
+    0x8457, //v4 =- v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6405, //v4 := 0x05
+    0x8446, //v4 >>= v4
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6481, //v4 := 0x81
+    0x844E, //v4 <<= v4
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6F05, //vF := 0x05
+    0x640F, //v4 := 0x0F
+    0x65F0, //v5 := 0xF0
+    0x8451, //v4 |= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6F05, //vF := 0x05
+    0x640C, //v4 := 0x0C
+    0x650A, //v5 := 0x0A
+    0x8452, //v4 &= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6F05, //vF := 0x05
+    0x640C, //v4 := 0x0C
+    0x650A, //v5 := 0x0A
+    0x8453, //v4 ^= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6F10, //vF := 0x10
+    0x6501, //v5 := 0x01
+    0x8F54, //vF += v5
+    0x80F0, //v0 := vF
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6FFF, //vF := 0xFF
+    0x6401, //v4 := 0x01
+    0x84F4, //v4 += vF
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    0x6F03, //vF := 0x03
+    0x64FF, //v4 := 0xFF
+    0x7402, //v4 += 0x02
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x22B2, //call show
+    //halt, 0x2B0
+    0x12B0, //jump halt
+    //show, 0x2B2
+    0x8200, //v2 := v0
+    0x8226, //v2 >>= v2
+    0x8226, //v2 >>= v2
+    0x8226, //v2 >>= v2
+    0x8226, //v2 >>= v2
+    0xF229, //i := hex v2
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0x8200, //v2 := v0
+    0x630F, //v3 := 0x0F
+    0x8232, //v2 &= v3
+    0xF229, //i := hex v2
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0x8210, //v2 := v1
+    0x8232, //v2 &= v3
+    0xF229, //i := hex v2
+    0xDAB5, //sprite vA vB 5
+    0x7A0A, //vA += 0x0A
+    0x3A3C, //skip if vA == 0x3C
+    0x00EE, //return
+    0x6A00, //vA := 0x00
+    0x7B06, //vB += 0x06
+    0x00EE, //return
+];
+
+//Shows the values that tell the quirks apart, three to a row as in the flags rom:
+//VF after an and (logic), a shift of 0x10 into a register holding 4 (shift), the
+//two bytes left by saving 0x11 and then 0x22 (memory), 1 or 2 for where jump0
+//went (jump), and the frames taken by 40 sprites (vblank). A sprite drawn off
+//the right edge and another off the bottom show wrap or clip
+pub const QUIRKS: &[u16] = &[
+    0x00E0, //clear
+    0x6A00, //vA := 0x00
+    0x6B00, //vB := 0x00
+    0x6F05, //vF := 0x05
+    0x6403, //v4 := 0x03
+    0x6505, //v5 := 0x05
+    0x8452, //v4 &= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x2274, //call show
+    0x6404, //v4 := 0x04
+    0x6510, //v5 := 0x10
+    0x8456, //v4 >>= v5
+    0x8040, //v0 := v4
+    0x81F0, //v1 := vF
+    0x2274, //call show
+    0xA2AA, //i := buf
+    0x6011, //v0 := 0x11
+    0xF055, //save v0 - v0
+    0x6022, //v0 := 0x22
+    0xF055, //save v0 - v0
+    0xA2AA, //i := buf
+    0xF165, //load v0 - v1
+    0x2274, //call show
+    0x6000, //v0 := 0x00
+    0x6202, //v2 := 0x02
+    0xB236, //jump0 targets
+    //targets, 0x236
+    0x123A, //jump plain
+    0x123E, //jump offset
+    //plain, 0x23A
+    0x6001, //v0 := 0x01
+    0x1240, //jump jumped
+    //offset, 0x23E
+    0x6002, //v0 := 0x02
+    //jumped, 0x240
+    0x6100, //v1 := 0x00
+    0x2274, //call show
+    0x64FF, //v4 := 0xFF
+    0xF415, //delay := v4
+    0x6614, //v6 := 0x14
+    0xA2A4, //i := dot
+    0x673F, //v7 := 0x3F
+    0x681F, //v8 := 0x1F
+    //sprites, 0x250
+    0xD781, //sprite v7 v8 1
+    0xD781, //sprite v7 v8 1
+    0x76FF, //v6 += 0xFF
+    0x3600, //skip if v6 == 0x00
+    0x1250, //jump sprites
+    0xF407, //v4 := delay
+    0x60FF, //v0 := 0xFF
+    0x8045, //v0 -= v4
+    0x6100, //v1 := 0x00
+    0x2274, //call show
+    0xA2A6, //i := block
+    0x643C, //v4 := 0x3C
+    0x6514, //v5 := 0x14
+    0xD454, //sprite v4 v5 4
+    0x6438, //v4 := 0x38
+    0x651E, //v5 := 0x1E
+    0xD454, //sprite v4 v5 4
+    //halt, 0x272
+    0x1272, //jump halt
+    //show, 0x274
+    0x8200, //v2 := v0
+    0x8226, //v2 >>= v2
+    0x8226, //v2 >>= v2
+    0x8226, //v2 >>= v2
+    0x8226, //v2 >>= v2
+    0xF229, //i := hex v2
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0x8200, //v2 := v0
+    0x630F, //v3 := 0x0F
+    0x8232, //v2 &= v3
+    0xF229, //i := hex v2
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0x8210, //v2 := v1
+    0x8232, //v2 &= v3
+    0xF229, //i := hex v2
+    0xDAB5, //sprite vA vB 5
+    0x7A0A, //vA += 0x0A
+    0x3A3C, //skip if vA == 0x3C
+    0x00EE, //return
+    0x6A00, //vA := 0x00
+    0x7B06, //vB += 0x06
+    0x00EE, //return
+    //dot, 0x2A4
+    0x8000, //dot
+    //block, 0x2A6
+    0xFFFF, 0xFFFF, //block
+    //buf, 0x2AA
+    0x0000, //buf
+];
+
+//Waits for two keys and draws them, then draws a 5 once key 5 is held
+pub const KEYPAD: &[u16] = &[
+    0x00E0, //clear
+    0x6A02, //vA := 0x02
+    0x6B02, //vB := 0x02
+    0xF00A, //v0 := key
+    0xF029, //i := hex v0
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0xF00A, //v0 := key
+    0xF029, //i := hex v0
+    0xDAB5, //sprite vA vB 5
+    0x7A05, //vA += 0x05
+    0x6305, //v3 := 0x05
+    //poll, 0x218
+    0xE3A1, //skip if key v3 is not pressed
+    0x121E, //jump held
+    0x1218, //jump poll
+    //held, 0x21E
+    0xF329, //i := hex v3
+    0xDAB5, //sprite vA vB 5
+    //halt, 0x222
+    0x1222, //jump halt
+];
+
+//Sounds the buzzer for half a second and draws a note
+pub const BEEP: &[u16] = &[
+    0x00E0, //clear
+    0x601E, //v0 := 0x1E
+    0xF018, //buzzer := v0
+    0xA210, //i := note
+    0x601C, //v0 := 0x1C
+    0x610C, //v1 := 0x0C
+    0xD018, //sprite v0 v1 8
+    //halt, 0x20E
+    0x120E, //jump halt
+    //note, 0x210
+    0x0C0E, 0x0B09, 0x0838, 0x7830, //note
+];
@@ -0,0 +1,162 @@
+//Runs the core and the simple reference interpreter in lock-step on the conformance
+//roms and on random ones, failing at the first register, RAM or screen difference
+
+#[path = "conformance/roms.rs"]
+mod roms;
+
+use chip8_emulator::{
+    chip8::{
+        quirks::{Platform, Quirks},
+        Chip8,
+    },
+    reference::{compare, Lockstep},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+//Every documented instruction as (mask, pattern), the bits outside the mask are free
+const INSTRUCTIONS: [(u16, u16); 34] = [
+    (0xFFFF, 0x00E0),
+    (0xFFFF, 0x00EE),
+    (0xF000, 0x1000),
+    (0xF000, 0x2000),
+    (0xF000, 0x3000),
+    (0xF000, 0x4000),
+    (0xF00F, 0x5000),
+    (0xF000, 0x6000),
+    (0xF000, 0x7000),
+    (0xF00F, 0x8000),
+    (0xF00F, 0x8001),
+    (0xF00F, 0x8002),
+    (0xF00F, 0x8003),
+    (0xF00F, 0x8004),
+    (0xF00F, 0x8005),
+    (0xF00F, 0x8006),
+    (0xF00F, 0x8007),
+    (0xF00F, 0x800E),
+    (0xF00F, 0x9000),
+    (0xF000, 0xA000),
+    (0xF000, 0xB000),
+    (0xF000, 0xC000),
+    (0xF000, 0xD000),
+    (0xF0FF, 0xE09E),
+    (0xF0FF, 0xE0A1),
+    (0xF0FF, 0xF007),
+    (0xF0FF, 0xF00A),
+    (0xF0FF, 0xF015),
+    (0xF0FF, 0xF018),
+    (0xF0FF, 0xF01E),
+    (0xF0FF, 0xF029),
+    (0xF0FF, 0xF033),
+    (0xF0FF, 0xF055),
+    (0xF0FF, 0xF065),
+];
+const RANDOM_ROMS: u64 = 200;
+const RANDOM_FRAMES: u64 = 30;
+
+fn bytes(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+//Mostly real instructions with jumps and calls landing inside the rom, so programs loop
+//and draw instead of falling off the end. One word in 16 is raw and often unknown
+fn random_rom(rng: &mut StdRng) -> Vec<u8> {
+    let len = rng.gen_range(4..64);
+    let words: Vec<u16> = (0..len)
+        .map(|_| {
+            let raw = rng.gen_range(0x0000..=0xFFFF);
+            if rng.gen_range(0..16) == 0 {
+                return raw;
+            }
+            let (mask, pattern) = INSTRUCTIONS[rng.gen_range(0..INSTRUCTIONS.len())];
+            match pattern {
+                0x1000 | 0x2000 => pattern | (0x200 + 2 * rng.gen_range(0..len)),
+                _ => pattern | (raw & !mask),
+            }
+        })
+        .collect();
+    bytes(&words)
+}
+
+fn random_quirks(rng: &mut StdRng) -> Quirks {
+    Quirks {
+        logic: rng.gen_bool(0.5),
+        shift: rng.gen_bool(0.5),
+        memory_leave_i: rng.gen_bool(0.5),
+        jump: rng.gen_bool(0.5),
+        wrap: rng.gen_bool(0.5),
+        vblank: rng.gen_bool(0.5),
+    }
+}
+
+#[test]
+fn conformance_roms_agree() {
+    let roms = [
+        ("logo", roms::LOGO),
+        ("opcodes", roms::OPCODES),
+        ("flags", roms::FLAGS),
+        ("quirks", roms::QUIRKS),
+        ("keypad", roms::KEYPAD),
+        ("beep", roms::BEEP),
+    ];
+    let keys = [(10, 0x1, true), (12, 0x1, false), (20, 0xA, true), (22, 0xA, false), (30, 0x5, true)];
+    for (name, rom) in roms {
+        for platform in [Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+            let mut lockstep = Lockstep::new(&bytes(rom), platform.quirks(), 0);
+            for frame in 0..120 {
+                for &(_, key, pressed) in keys.iter().filter(|(at, ..)| *at == frame) {
+                    lockstep.set_key(key, pressed).unwrap();
+                }
+                if let Err(e) = lockstep.run_frame(platform.tick_rate()) {
+                    panic!("{} on {}: {}", name, platform, e);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn random_roms_agree() {
+    for seed in 0..RANDOM_ROMS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let rom = random_rom(&mut rng);
+        let quirks = random_quirks(&mut rng);
+        let ipf = rng.gen_range(1..=30);
+        let mut lockstep = Lockstep::new(&rom, quirks, seed);
+        let result = (0..RANDOM_FRAMES).try_for_each(|_| {
+            if rng.gen_range(0..4) == 0 {
+                lockstep.set_key(rng.gen_range(0..16), rng.gen_bool(0.5))?;
+            }
+            lockstep.run_frame(ipf)
+        });
+        if let Err(e) = result {
+            panic!("Seed {} with {:?} and {} per frame: {}\nRom: {:02X?}", seed, quirks, ipf, e, rom);
+        }
+    }
+}
+
+#[test]
+fn differences_are_reported() {
+    let core = Chip8::new().snapshot();
+    assert_eq!(compare(&core, &core), None);
+
+    let mut reference = core.clone();
+    reference.reg[3] = 0x07;
+    assert_eq!(
+        compare(&core, &reference).unwrap(),
+        "V3 is 0x00 in Chip8 but 0x07 in the reference"
+    );
+
+    let mut reference = core.clone();
+    reference.ram[0x300] = 0xAB;
+    assert_eq!(
+        compare(&core, &reference).unwrap(),
+        "RAM at 0x300 is 0x00 in Chip8 but 0xAB in the reference"
+    );
+
+    let mut reference = core.clone();
+    reference.screen[8 * 2 + 1] = 0x20;
+    assert_eq!(
+        compare(&core, &reference).unwrap(),
+        "The pixel at 10, 2 is off in Chip8 but on in the reference"
+    );
+}
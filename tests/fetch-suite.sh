@@ -0,0 +1,12 @@
+#!/bin/sh
+#Downloads the roms of the community test suite into tests/roms along with its GPL license,
+#so they can be committed. REF picks the branch, tag or commit to take them from
+set -eu
+cd "$(dirname "$0")"
+mkdir -p roms
+base="https://raw.githubusercontent.com/Timendus/chip8-test-suite/${REF:-main}"
+for rom in 1-chip8-logo 2-ibm-logo 3-corax+ 4-flags 5-quirks 6-keypad 7-beep; do
+    curl -fsSL "$base/bin/$rom.ch8" -o "roms/$rom.ch8"
+done
+curl -fsSL "$base/LICENSE" -o roms/LICENSE
+echo "Fetched the suite into tests/roms"
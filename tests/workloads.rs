@@ -0,0 +1,41 @@
+//The benchmark workloads should measure what they say, so each one has to keep running
+//its own class of instruction without faulting
+use chip8_emulator::{
+    chip8::Chip8,
+    workloads::{class, run, workloads, CLASSES},
+};
+
+#[test]
+fn workloads_stay_in_their_class() {
+    for workload in workloads() {
+        assert!(CLASSES.contains(&workload.class));
+        let mut cpu = workload.cpu();
+        let mut own = 0;
+        for n in 1..=10_000 {
+            if cpu.opcode().map(class) == Some(workload.class) {
+                own += 1;
+            }
+            cpu.update();
+            if n % 15 == 0 {
+                cpu.update_timers();
+            }
+        }
+        assert_eq!(cpu.error(), None, "{} hit an error", workload.class);
+        assert!(own > 9_000, "{} ran {} of its own instructions in 10000", workload.class, own);
+    }
+}
+
+#[test]
+fn runs_stop_with_the_rom() {
+    for workload in workloads() {
+        assert_eq!(run(&mut workload.cpu(), 1000, 15), 1000, "{} stopped", workload.class);
+    }
+    //Waiting for a key after the load and Fx0A, then nothing more runs
+    let mut cpu = Chip8::new().with_rom(&[0x60, 0x00, 0xF0, 0x0A, 0x12, 0x00]);
+    assert_eq!(run(&mut cpu, 1000, 15), 2);
+    assert_eq!(run(&mut cpu, 1000, 15), 0);
+    //The unknown opcode is decoded, the error screen after it is not counted
+    let mut cpu = Chip8::new().with_rom(&[0x60, 0x00, 0xFF, 0xFF]);
+    assert_eq!(run(&mut cpu, 1000, 15), 2);
+    assert!(cpu.error().is_some());
+}